@@ -16,6 +16,27 @@ export interface WorkflowRunResult<T = unknown> {
   steps: string[];
 }
 
+export interface WorkflowRunHandle {
+  executionId: string;
+}
+
+/**
+ * A workflow descriptor that can be called like the workflow function itself.
+ * Calling it is the same as calling `.start()`.
+ */
+export interface CallableWorkflow<TInput = unknown, TResult = unknown>
+  extends WorkflowDescriptor {
+  (input: TInput, opts?: StartOptions): Promise<WorkflowRunResult<TResult>>;
+  /** Run the workflow and wait for its result */
+  start(input: TInput, opts?: StartOptions): Promise<WorkflowRunResult<TResult>>;
+  /** Start the workflow without waiting for it to finish */
+  startAsync(input: TInput, opts?: StartOptions): Promise<WorkflowRunHandle>;
+  /** Look up a run by execution ID */
+  getRun(executionId: string): Promise<WorkflowRunResult<TResult>>;
+  /** Cancel a running execution */
+  cancel(executionId: string): Promise<void>;
+}
+
 export interface StartOptions {
   /** AWS region override */
   region?: string;
@@ -44,12 +65,7 @@ export async function start<T = unknown>(
 
   const command = new InvokeCommand({
     FunctionName: workflow.functionName,
-    Payload: new TextEncoder().encode(
-      JSON.stringify({
-        input,
-        executionId: `exec-${Date.now()}-${Math.random().toString(36).slice(2, 9)}`,
-      })
-    ),
+    Payload: encodeEvent(input, newExecutionId()),
   });
 
   const response = await client.send(command);
@@ -87,3 +103,87 @@ export async function getRun<T = unknown>(
       "In production, this would query a DynamoDB table or Step Functions execution."
   );
 }
+
+/**
+ * Start a workflow execution without waiting for it to finish. The Lambda is
+ * invoked asynchronously; use the returned execution ID to look the run up.
+ *
+ * @param workflow - The workflow descriptor
+ * @param input - The input payload for the workflow
+ * @param opts - Optional configuration
+ * @returns A handle carrying the execution ID
+ */
+export async function startAsync(
+  workflow: WorkflowDescriptor,
+  input: unknown,
+  opts?: StartOptions
+): Promise<WorkflowRunHandle> {
+  if (!workflow.__workflow) {
+    throw new Error("Invalid workflow descriptor. Expected a transformed workflow import.");
+  }
+
+  const executionId = newExecutionId();
+  const client = new LambdaClient(opts?.clientConfig ?? {});
+  await client.send(
+    new InvokeCommand({
+      FunctionName: workflow.functionName,
+      InvocationType: "Event",
+      Payload: encodeEvent(input, executionId),
+    })
+  );
+
+  return { executionId };
+}
+
+/**
+ * Cancel a running workflow execution.
+ * (Placeholder — in production this would stop the durable execution.)
+ *
+ * @param workflow - The workflow descriptor
+ * @param executionId - The execution ID to cancel
+ */
+export async function cancel(
+  _workflow: WorkflowDescriptor,
+  _executionId: string
+): Promise<void> {
+  throw new Error(
+    "cancel() is not yet implemented. " +
+      "In production, this would stop the durable execution."
+  );
+}
+
+/**
+ * Build a callable workflow from its descriptor. Client mode emits a call to
+ * this for every workflow import, so handlers can `await signupWorkflow(input)`.
+ *
+ * @param descriptor - The workflow name and the Lambda function running it
+ * @returns The callable workflow
+ */
+export function defineWorkflow<TInput = unknown, TResult = unknown>(descriptor: {
+  name: string;
+  functionName: string;
+}): CallableWorkflow<TInput, TResult> {
+  const workflow = ((input: TInput, opts?: StartOptions) =>
+    start<TResult>(workflow, input, opts)) as CallableWorkflow<TInput, TResult>;
+
+  // `name` is a read-only property of functions, so it is defined rather than assigned
+  Object.defineProperties(workflow, {
+    __workflow: { value: true, enumerable: true },
+    name: { value: descriptor.name, enumerable: true },
+    functionName: { value: descriptor.functionName, enumerable: true },
+  });
+  workflow.start = (input, opts) => start<TResult>(workflow, input, opts);
+  workflow.startAsync = (input, opts) => startAsync(workflow, input, opts);
+  workflow.getRun = (executionId) => getRun<TResult>(workflow, executionId);
+  workflow.cancel = (executionId) => cancel(workflow, executionId);
+
+  return workflow;
+}
+
+function newExecutionId(): string {
+  return `exec-${Date.now()}-${Math.random().toString(36).slice(2, 9)}`;
+}
+
+function encodeEvent(input: unknown, executionId: string): Uint8Array {
+  return new TextEncoder().encode(JSON.stringify({ input, executionId }));
+}
@@ -3,7 +3,7 @@
  */
 
 import { transform } from "@swc/core";
-import { resolve, dirname } from "node:path";
+import { resolve, dirname, basename, extname, relative } from "node:path";
 import { existsSync, promises as fsp } from "node:fs";
 import { fileURLToPath } from "node:url";
 
@@ -13,14 +13,39 @@ const __dirname = dirname(__filename);
 export interface DurablePluginOptions {
   /** Glob patterns for workflow files (transformed in workflow mode) */
   workflowPatterns?: string[];
+  /** Glob patterns for shared step modules (transformed in workflow mode) */
+  stepPatterns?: string[];
   /** Glob patterns for client files (transformed in client mode) */
   clientPatterns?: string[];
   /** Package name for SDK import. Default: "@cgalceran/aws-durable" */
   packageName?: string;
   /** Environment variable prefix for workflow function names. Default: "WORKFLOW_" */
   envPrefix?: string;
+  /** How workflows call their own steps. Default: "inline" (copy the body into each call) */
+  stepStrategy?: "inline" | "closure";
+  /** Env var spelling of workflow names. Default: "screamingSnake" (WORKFLOW_SIGNUP_WORKFLOW) */
+  envNaming?: "screamingSnake" | "uppercase" | "preserve";
   /** Path to the WASM plugin file. Auto-detected if not specified. */
   pluginPath?: string;
+  /** Determinism linter for workflow bodies. Default: { level: "warn" } */
+  determinism?: {
+    level?: "off" | "warn" | "error";
+    /** Rule names to skip, e.g. "Date.now" or "process.env" */
+    allow?: string[];
+  };
+  /**
+   * Directory to write workflow manifests to, one `<file>.workflow.json` per
+   * workflow module at its path relative to cwd. Manifests are not written
+   * when unset.
+   */
+  manifestDir?: string;
+  /**
+   * Workflow manifest, as written to `manifestDir`, telling client mode which
+   * imports refer to workflow modules.
+   */
+  workflowManifest?: {
+    workflows: Record<string, { file?: string; handler?: string }>;
+  };
 }
 
 function resolvePluginWasm(customPath?: string): string {
@@ -52,15 +77,47 @@ function matchesPattern(filePath: string, patterns: string[]): boolean {
   });
 }
 
+async function writeManifest(
+  output: string | undefined,
+  filename: string,
+  manifestDir: string
+): Promise<void> {
+  // The plugin emits the manifest JSON under `workflowManifest` in the
+  // transform output, a JSON object of strings
+  const manifestJson: string | undefined = output
+    ? JSON.parse(output).workflowManifest
+    : undefined;
+  if (!manifestJson) return;
+
+  // Mirrors the module's path relative to cwd, so `order/index.ts` and
+  // `signup/index.ts` do not overwrite each other's manifest
+  const outFile = resolve(
+    manifestDir,
+    dirname(filename),
+    `${basename(filename, extname(filename))}.workflow.json`
+  );
+  await fsp.mkdir(dirname(outFile), { recursive: true });
+  await fsp.writeFile(
+    outFile,
+    JSON.stringify(JSON.parse(manifestJson), null, 2) + "\n"
+  );
+}
+
 export function durablePlugin(options: DurablePluginOptions = {}): {
   name: string;
   setup: (build: { onLoad: Function }) => void;
 } {
   const {
     workflowPatterns = ["**/workflows/**", "**/*.workflow.*"],
+    stepPatterns = ["**/steps/**", "**/*.step.*"],
     clientPatterns = ["**/handlers/**", "**/*.handler.*", "**/api/**"],
     packageName = "@cgalceran/aws-durable",
     envPrefix = "WORKFLOW_",
+    envNaming,
+    stepStrategy,
+    determinism,
+    manifestDir,
+    workflowManifest,
   } = options;
 
   const pluginWasm = resolvePluginWasm(options.pluginPath);
@@ -72,12 +129,16 @@ export function durablePlugin(options: DurablePluginOptions = {}): {
         { filter: /\.(ts|tsx|js|jsx)$/ },
         async (args: { path: string }) => {
           const filePath = args.path;
+          const filename = relative(process.cwd(), filePath);
           const source = await fsp.readFile(filePath, "utf-8");
 
           // Determine transform mode based on file path
           let mode: "workflow" | "client" | null = null;
 
-          if (matchesPattern(filePath, workflowPatterns)) {
+          if (
+            matchesPattern(filePath, workflowPatterns) ||
+            matchesPattern(filePath, stepPatterns)
+          ) {
             // Only transform if file actually has directives
             if (
               source.includes('"use workflow"') ||
@@ -97,6 +158,9 @@ export function durablePlugin(options: DurablePluginOptions = {}): {
 
           const result = await transform(source, {
             filename: filePath,
+            // Inline maps are picked up by esbuild, so bundles map back to the
+            // original workflow and step sources
+            sourceMaps: "inline",
             jsc: {
               parser: {
                 syntax: "typescript",
@@ -110,6 +174,15 @@ export function durablePlugin(options: DurablePluginOptions = {}): {
                       mode,
                       packageName,
                       envPrefix,
+                      envNaming,
+                      stepStrategy,
+                      determinism,
+                      filename,
+                      // Client mode resolves workflow imports with the same globs
+                      workflowPatterns: options.workflowPatterns,
+                      // Workflow mode turns calls into these modules into steps
+                      stepPatterns: options.stepPatterns,
+                      workflowManifest,
                     },
                   ],
                 ],
@@ -118,6 +191,10 @@ export function durablePlugin(options: DurablePluginOptions = {}): {
             },
           });
 
+          if (mode === "workflow" && manifestDir) {
+            await writeManifest(result.output, filename, manifestDir);
+          }
+
           return {
             contents: result.code,
             loader: "js" as const,
@@ -19,6 +19,9 @@ export interface WorkflowDescriptor {
   functionName: string;
 }
 
+export type { CallableWorkflow, WorkflowRunHandle } from "./client";
+export type { RetryPolicy } from "./runtime";
+
 /**
  * Invoke another Lambda function as a durable step.
  * Transformed by the SWC plugin into a ctx.step() with LambdaClient invocation.
@@ -40,6 +43,42 @@ export declare function invoke<T = unknown>(
  */
 export declare function sleep(duration: Duration): Promise<void>;
 
+/** Options for map() */
+export interface MapOptions {
+  /** Elements processed at once. Default: all of them */
+  concurrency?: number;
+}
+
+/**
+ * Run a step for every element of an array, each element checkpointed as its
+ * own step. Transformed by the SWC plugin into a ctx.map() call.
+ *
+ * @param items - The elements to process
+ * @param step - A "use step" function, called with each element and its index
+ * @param opts - Optional concurrency limit
+ * @returns The step results, in element order
+ */
+export declare function map<T, R>(
+  items: readonly T[],
+  step: (item: T, index: number) => Promise<R>,
+  opts?: MapOptions
+): Promise<R[]>;
+
+/**
+ * Register a step undoing the work of `step` once it succeeds. If the workflow
+ * fails later on, registered compensations run in reverse order, each
+ * checkpointed as its own step. Transformed by the SWC plugin into a
+ * ctx.compensate() call.
+ *
+ * @param step - A step call, e.g. `chargeCard(order)`
+ * @param undo - A "use step" function, called with the result of `step`
+ * @returns The result of `step`
+ */
+export declare function compensate<T>(
+  step: Promise<T>,
+  undo: (result: T) => Promise<unknown>
+): Promise<T>;
+
 /**
  * Options for waitForCallback */
 export interface WaitForCallbackOptions {
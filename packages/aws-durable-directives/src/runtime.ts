@@ -3,9 +3,60 @@
  * This is the actual runtime code imported by the transformed workflow modules.
  */
 
+// Client helpers, imported by modules transformed in client mode
+export { defineWorkflow, start, startAsync, getRun, cancel } from "./client";
+
 export interface DurableContext {
-  /** Execute a named step with automatic checkpointing. */
-  step<T>(name: string, fn: () => Promise<T>): Promise<T>;
+  /**
+   * Execute a named step with automatic checkpointing.
+   *
+   * Names emitted by the compiler are `<step>:<call-site ordinal>`. When the
+   * same name runs more than once in an execution (a step inside a loop), the
+   * second and later runs are checkpointed as `<name>#<occurrence>`.
+   *
+   * With a `retry` policy, a failed attempt is retried before the step fails;
+   * only the successful result is checkpointed. An attempt running longer
+   * than `timeout` fails with a `StepTimeoutError`, which is retried like any
+   * other error.
+   */
+  step<T>(name: string, fn: () => Promise<T>, opts?: StepOptions): Promise<T>;
+
+  /**
+   * Run branches concurrently, combined like the `Promise` method `mode`
+   * names. Each branch gets a child context whose steps are checkpointed as
+   * `<name>/<branch index>/<step>`, and the combined outcome is checkpointed
+   * as `<name>` so replay resolves the same way, `race` included.
+   */
+  parallel<T>(
+    name: string,
+    branches: Array<(ctx: DurableContext) => Promise<T>>,
+    opts?: { mode?: ParallelMode }
+  ): Promise<unknown>;
+
+  /**
+   * Run `fn` over `items`, at most `concurrency` elements at a time. Each
+   * element is its own step, checkpointed as `<name>/<index>`, so a partially
+   * processed batch resumes with the elements that had not finished.
+   */
+  map<T, R>(
+    name: string,
+    items: readonly T[],
+    fn: (item: T, index: number) => Promise<R>,
+    opts?: { concurrency?: number } & StepOptions
+  ): Promise<R[]>;
+
+  /**
+   * Await `forward` and register `undo` to be called with its result if the
+   * execution fails later on. Registered compensations run in reverse order,
+   * each checkpointed as the step `<name>`; see `withDurableExecution` for
+   * how their failures are reported.
+   */
+  compensate<T>(
+    name: string,
+    forward: Promise<T>,
+    undo: (result: T) => Promise<unknown>,
+    opts?: StepOptions
+  ): Promise<T>;
 
   /** Wait/sleep for a specified duration. */
   wait(duration: { seconds: number }): Promise<void>;
@@ -18,6 +69,70 @@ export interface DurableContext {
   ): Promise<T>;
 }
 
+/** Options of a single step. */
+export interface StepOptions {
+  retry?: RetryPolicy;
+  /** How long each attempt may run */
+  timeout?: Duration;
+}
+
+/** Options of a workflow. */
+export interface WorkflowOptions {
+  /** How long the whole execution may run */
+  timeout?: Duration;
+}
+
+/**
+ * How a failing step is retried, assigned next to the step as
+ * `chargeCard.retry = { ... }`.
+ */
+export interface RetryPolicy {
+  /** Attempts in total, the first one included. Default: 3 */
+  maxAttempts?: number;
+  /** Whether the delay doubles after every attempt. Default: "exponential" */
+  backoff?: "fixed" | "exponential";
+  /** Delay before the first retry. Default: 1 second */
+  initialDelay?: Duration;
+  /** Upper bound of the delay between attempts */
+  maxDelay?: Duration;
+  /** Wait a random fraction of each delay, spreading out retries. Default: false */
+  jitter?: boolean;
+  /** Retry only errors this returns true for. Default: every error */
+  retryIf?: (error: unknown) => boolean;
+}
+
+export interface Duration {
+  seconds?: number;
+  minutes?: number;
+  hours?: number;
+  days?: number;
+}
+
+/** Thrown by a step attempt that runs longer than its `timeout`. */
+export class StepTimeoutError extends Error {
+  constructor(
+    readonly stepName: string,
+    readonly timeout: Duration
+  ) {
+    super(`Step "${stepName}" timed out after ${formatDuration(timeout)}`);
+    this.name = "StepTimeoutError";
+  }
+}
+
+/** Thrown by a workflow execution that runs longer than its `timeout`. */
+export class WorkflowTimeoutError extends Error {
+  constructor(
+    readonly executionId: string,
+    readonly timeout: Duration
+  ) {
+    super(`Execution "${executionId}" timed out after ${formatDuration(timeout)}`);
+    this.name = "WorkflowTimeoutError";
+  }
+}
+
+/** How `ctx.parallel` combines its branches. */
+export type ParallelMode = "all" | "allSettled" | "race";
+
 export interface DurableEvent<T = unknown> {
   executionId: string;
   input: T;
@@ -32,66 +147,284 @@ export interface WorkflowHandler {
  * Wraps an async workflow function with durable execution support.
  * Handles step checkpointing, replay, and state persistence.
  *
+ * If the workflow fails, its registered compensations run in reverse order and
+ * the handler rethrows the workflow's error. When a compensation fails too,
+ * the rest still run and the handler throws an `AggregateError` of the
+ * compensation failures instead, with the workflow's error as its `cause`.
+ * Steps and compensations a body starts after it failed, such as a body still
+ * running past its timeout, throw instead of running.
+ *
  * @param fn - The workflow function receiving (event, ctx)
+ * @param opts - Optional timeout of the whole execution
  * @returns A Lambda-compatible handler function
+ * @throws The workflow's error, or an `AggregateError` if a compensation failed
  */
 export function withDurableExecution(
-  fn: (event: DurableEvent, ctx: DurableContext) => Promise<unknown>
+  fn: (event: DurableEvent, ctx: DurableContext) => Promise<unknown>,
+  opts?: WorkflowOptions
 ): WorkflowHandler {
   return async (rawEvent: unknown, _lambdaContext: unknown) => {
     const event = rawEvent as DurableEvent;
     const executionId =
       event.executionId || `exec-${Date.now()}-${Math.random().toString(36).slice(2, 9)}`;
 
-    const completedSteps = new Map<string, unknown>();
-    const stepLog: string[] = [];
-
-    const ctx: DurableContext = {
-      async step<T>(name: string, fn: () => Promise<T>): Promise<T> {
-        // Check if step was already completed (replay)
-        if (completedSteps.has(name)) {
-          return completedSteps.get(name) as T;
-        }
+    const state: ExecutionState = {
+      executionId,
+      completedSteps: new Map(),
+      stepOccurrences: new Map(),
+      stepLog: [],
+      compensations: [],
+      aborted: false,
+    };
+    const ctx = createContext(state, "");
 
-        stepLog.push(name);
-        const result = await fn();
-        completedSteps.set(name, result);
-        return result;
-      },
-
-      async wait(duration: { seconds: number }): Promise<void> {
-        // In a real implementation, this would pause execution
-        // and resume via Step Functions or a timer mechanism.
-        // For now, implement as actual delay for local testing.
-        await new Promise((resolve) =>
-          setTimeout(resolve, duration.seconds * 1000)
-        );
-      },
-
-      async waitForCallback<T>(
-        name: string,
-        setup?: (token: string) => void | Promise<void>,
-        _opts?: { timeout?: { seconds: number } }
-      ): Promise<T> {
-        const token = `${executionId}:${name}:${Date.now()}`;
-        if (setup) {
-          await setup(token);
+    let result: unknown;
+    try {
+      result = await withTimeout(
+        () => fn(event, ctx),
+        opts?.timeout,
+        (timeout) => new WorkflowTimeoutError(executionId, timeout)
+      );
+    } catch (error) {
+      // A timed-out body keeps running; stop it from starting more steps or
+      // registering compensations once rollback begins
+      state.aborted = true;
+      // Undo the work of the steps that succeeded, latest first. A failing
+      // compensation does not stop the ones registered before it.
+      const failures: unknown[] = [];
+      for (const compensation of [...state.compensations].reverse()) {
+        try {
+          await compensation();
+        } catch (failure) {
+          failures.push(failure);
         }
-        // In production, this would suspend and wait for callback delivery.
-        // For local development, throw to indicate callback is pending.
-        throw new Error(
-          `Callback "${name}" is pending with token: ${token}. ` +
-            `In production, execution suspends here until callback is received.`
+      }
+      if (failures.length > 0) {
+        throw new AggregateError(
+          failures,
+          `${failures.length} compensation(s) failed while rolling back execution ${executionId}`,
+          { cause: error }
         );
-      },
-    };
-
-    const result = await fn(event, ctx);
+      }
+      throw error;
+    }
 
     return {
       executionId,
       result,
-      steps: stepLog,
+      steps: state.stepLog,
     };
   };
 }
+
+/** Checkpoints shared by the workflow context and its parallel branches. */
+interface ExecutionState {
+  executionId: string;
+  completedSteps: Map<string, unknown>;
+  stepOccurrences: Map<string, number>;
+  stepLog: string[];
+  /** Registered by `ctx.compensate`, in order of registration. */
+  compensations: Array<() => Promise<unknown>>;
+  /** Set once the execution failed and its compensations run. */
+  aborted: boolean;
+}
+
+/** A durable context whose step names are prefixed with `prefix`. */
+function createContext(state: ExecutionState, prefix: string): DurableContext {
+  const { executionId, completedSteps, stepOccurrences, stepLog } = state;
+
+  /** Fail calls made by a workflow body that outlived its execution. */
+  const assertRunning = (name: string) => {
+    if (state.aborted) {
+      throw new Error(
+        `Execution "${executionId}" is rolling back; "${prefix}${name}" was not run`
+      );
+    }
+  };
+
+  /** Run `fn` as the step `name`, or return its checkpointed result. */
+  const checkpoint = async <T>(
+    name: string,
+    fn: () => Promise<T>,
+    opts?: StepOptions
+  ): Promise<T> => {
+    // Disambiguate repeated runs of one call site deterministically
+    const scoped = prefix + name;
+    const occurrence = (stepOccurrences.get(scoped) ?? 0) + 1;
+    stepOccurrences.set(scoped, occurrence);
+    const stepId = occurrence === 1 ? scoped : `${scoped}#${occurrence}`;
+
+    // Check if step was already completed (replay)
+    if (completedSteps.has(stepId)) {
+      return completedSteps.get(stepId) as T;
+    }
+
+    stepLog.push(stepId);
+    const attempt = () =>
+      withTimeout(fn, opts?.timeout, (timeout) => new StepTimeoutError(stepId, timeout));
+    const result = await withRetries(attempt, opts?.retry);
+    completedSteps.set(stepId, result);
+    return result;
+  };
+
+  const ctx: DurableContext = {
+    async step<T>(name: string, fn: () => Promise<T>, opts?: StepOptions): Promise<T> {
+      assertRunning(name);
+      return checkpoint(name, fn, opts);
+    },
+
+    async parallel<T>(
+      name: string,
+      branches: Array<(ctx: DurableContext) => Promise<T>>,
+      opts?: { mode?: ParallelMode }
+    ): Promise<unknown> {
+      return ctx.step<unknown>(name, async () => {
+        const running = branches.map((branch, index) =>
+          branch(createContext(state, `${prefix}${name}/${index}/`))
+        );
+        switch (opts?.mode ?? "all") {
+          case "allSettled":
+            return Promise.allSettled(running);
+          case "race":
+            return Promise.race(running);
+          default:
+            return Promise.all(running);
+        }
+      });
+    },
+
+    async map<T, R>(
+      name: string,
+      items: readonly T[],
+      fn: (item: T, index: number) => Promise<R>,
+      opts?: { concurrency?: number } & StepOptions
+    ): Promise<R[]> {
+      const results = new Array<R>(items.length);
+      const workers = Math.max(1, Math.min(opts?.concurrency ?? items.length, items.length));
+      let next = 0;
+      const work = async () => {
+        while (next < items.length) {
+          const index = next++;
+          results[index] = await ctx.step(`${name}/${index}`, () => fn(items[index], index), {
+            retry: opts?.retry,
+            timeout: opts?.timeout,
+          });
+        }
+      };
+      await Promise.all(Array.from({ length: workers }, work));
+      return results;
+    },
+
+    async compensate<T>(
+      name: string,
+      forward: Promise<T>,
+      undo: (result: T) => Promise<unknown>,
+      opts?: StepOptions
+    ): Promise<T> {
+      const result = await forward;
+      assertRunning(name);
+      // Compensations run during rollback, past the abort check of ctx.step
+      state.compensations.push(() => checkpoint(name, () => undo(result), opts));
+      return result;
+    },
+
+    async wait(duration: { seconds: number }): Promise<void> {
+      // In a real implementation, this would pause execution
+      // and resume via Step Functions or a timer mechanism.
+      // For now, implement as actual delay for local testing.
+      await new Promise((resolve) =>
+        setTimeout(resolve, duration.seconds * 1000)
+      );
+    },
+
+    async waitForCallback<T>(
+      name: string,
+      setup?: (token: string) => void | Promise<void>,
+      _opts?: { timeout?: { seconds: number } }
+    ): Promise<T> {
+      const token = `${executionId}:${prefix}${name}:${Date.now()}`;
+      if (setup) {
+        await setup(token);
+      }
+      // In production, this would suspend and wait for callback delivery.
+      // For local development, throw to indicate callback is pending.
+      throw new Error(
+        `Callback "${name}" is pending with token: ${token}. ` +
+          `In production, execution suspends here until callback is received.`
+      );
+    },
+  };
+
+  return ctx;
+}
+
+/** Run `fn`, retrying failed attempts as `policy` allows. */
+async function withRetries<T>(fn: () => Promise<T>, policy?: RetryPolicy): Promise<T> {
+  if (!policy) {
+    return fn();
+  }
+  const maxAttempts = policy.maxAttempts ?? 3;
+  for (let attempt = 1; ; attempt++) {
+    try {
+      return await fn();
+    } catch (error) {
+      if (attempt >= maxAttempts || (policy.retryIf && !policy.retryIf(error))) {
+        throw error;
+      }
+      await new Promise((resolve) => setTimeout(resolve, retryDelayMs(policy, attempt)));
+    }
+  }
+}
+
+/** Delay after failed attempt number `attempt`, counting from 1. */
+function retryDelayMs(policy: RetryPolicy, attempt: number): number {
+  const initial = durationMs(policy.initialDelay ?? { seconds: 1 });
+  let delay = policy.backoff === "fixed" ? initial : initial * 2 ** (attempt - 1);
+  if (policy.maxDelay) {
+    delay = Math.min(delay, durationMs(policy.maxDelay));
+  }
+  return policy.jitter ? Math.random() * delay : delay;
+}
+
+/** Run `fn`, failing with `onTimeout(timeout)` if it runs longer than `timeout`. */
+async function withTimeout<T>(
+  fn: () => Promise<T>,
+  timeout: Duration | undefined,
+  onTimeout: (timeout: Duration) => Error
+): Promise<T> {
+  if (!timeout) {
+    return fn();
+  }
+  let timer: ReturnType<typeof setTimeout> | undefined;
+  const expired = new Promise<never>((_, reject) => {
+    timer = setTimeout(() => reject(onTimeout(timeout)), durationMs(timeout));
+  });
+  try {
+    return await Promise.race([fn(), expired]);
+  } finally {
+    clearTimeout(timer);
+  }
+}
+
+function durationMs(duration: Duration): number {
+  const seconds =
+    (duration.days ?? 0) * 86400 +
+    (duration.hours ?? 0) * 3600 +
+    (duration.minutes ?? 0) * 60 +
+    (duration.seconds ?? 0);
+  return seconds * 1000;
+}
+
+/** `1h 30m`, `45s` */
+function formatDuration(duration: Duration): string {
+  const parts = [
+    [duration.days, "d"],
+    [duration.hours, "h"],
+    [duration.minutes, "m"],
+    [duration.seconds, "s"],
+  ] as const;
+  return parts
+    .filter(([amount]) => amount)
+    .map(([amount, unit]) => `${amount}${unit}`)
+    .join(" ") || "0s";
+}
@@ -0,0 +1,102 @@
+import { test } from "node:test";
+import assert from "node:assert/strict";
+
+import { withDurableExecution, type DurableContext } from "../src/runtime";
+
+const event = { executionId: "exec-test", input: {} };
+
+const delay = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+/** A step whose undo records its name in `undone`, optionally failing. */
+function undoStep(undone: string[], name: string, failure?: Error) {
+  return async () => {
+    undone.push(name);
+    if (failure) throw failure;
+  };
+}
+
+test("compensations run latest first and the workflow error is rethrown", async () => {
+  const undone: string[] = [];
+  const failure = new Error("ship failed");
+  const handler = withDurableExecution(async (_event, ctx) => {
+    await ctx.compensate("compensate:refund:1", ctx.step("charge:1", async () => 1), undoStep(undone, "refund"));
+    await ctx.compensate("compensate:release:1", ctx.step("reserve:1", async () => 2), undoStep(undone, "release"));
+    throw failure;
+  });
+
+  await assert.rejects(handler(event, {}), (error) => error === failure);
+  assert.deepEqual(undone, ["release", "refund"]);
+});
+
+test("a failing compensation does not stop the others", async () => {
+  const undone: string[] = [];
+  const failure = new Error("ship failed");
+  const releaseFailure = new Error("release failed");
+  const handler = withDurableExecution(async (_event, ctx) => {
+    await ctx.compensate("compensate:refund:1", ctx.step("charge:1", async () => 1), undoStep(undone, "refund"));
+    await ctx.compensate(
+      "compensate:release:1",
+      ctx.step("reserve:1", async () => 2),
+      undoStep(undone, "release", releaseFailure)
+    );
+    await ctx.compensate("compensate:notify:1", ctx.step("email:1", async () => 3), undoStep(undone, "notify"));
+    throw failure;
+  });
+
+  await assert.rejects(handler(event, {}), (error) => {
+    assert.ok(error instanceof AggregateError);
+    assert.deepEqual(error.errors, [releaseFailure]);
+    assert.equal(error.cause, failure);
+    return true;
+  });
+  assert.deepEqual(undone, ["notify", "release", "refund"]);
+});
+
+test("compensations registered in parallel branches are undone", async () => {
+  const undone: string[] = [];
+  const failure = new Error("stock unavailable");
+  const handler = withDurableExecution(async (_event, ctx) => {
+    await ctx.parallel("parallel:1", [
+      (branch: DurableContext) =>
+        branch.compensate("compensate:refund:1", branch.step("charge:1", async () => 1), undoStep(undone, "refund")),
+      async (branch: DurableContext) => {
+        await delay(10);
+        return branch.step("reserve:1", async () => {
+          throw failure;
+        });
+      },
+    ]);
+  });
+
+  await assert.rejects(handler(event, {}), (error) => error === failure);
+  assert.deepEqual(undone, ["refund"]);
+});
+
+test("a timed-out body cannot run steps or register compensations during rollback", async () => {
+  const undone: string[] = [];
+  const ran: string[] = [];
+  let lateCall: Promise<unknown> | undefined;
+  const handler = withDurableExecution(
+    async (_event, ctx: DurableContext) => {
+      await ctx.compensate("compensate:refund:1", ctx.step("charge:1", async () => 1), undoStep(undone, "refund"));
+      await delay(50);
+      lateCall = ctx.compensate(
+        "compensate:release:1",
+        ctx.step("reserve:1", async () => {
+          ran.push("reserve");
+          return 2;
+        }),
+        undoStep(undone, "release")
+      );
+      await lateCall;
+    },
+    { timeout: { seconds: 0.01 } }
+  );
+
+  await assert.rejects(handler(event, {}), { name: "WorkflowTimeoutError" });
+  await delay(60);
+  assert.ok(lateCall, "the body should keep running after its timeout");
+  await assert.rejects(lateCall, /is rolling back/);
+  assert.deepEqual(ran, []);
+  assert.deepEqual(undone, ["refund"]);
+});
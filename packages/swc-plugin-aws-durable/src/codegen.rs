@@ -1,6 +1,15 @@
-use swc_core::common::DUMMY_SP;
+use swc_core::common::{Span, Spanned, DUMMY_SP};
 use swc_core::ecma::ast::*;
 
+use crate::config::EnvNaming;
+use crate::meta::{manifest_json, StepExportMeta, WorkflowMeta};
+
+/// Identifies how step ids are derived, recorded in `__workflowMeta` so replay
+/// tooling can tell whether checkpoints from an older deployment still line up:
+/// `<step>:<call-site ordinal>`, with `#<occurrence>` appended at runtime when
+/// one call site runs more than once.
+pub const STEP_ID_SCHEME: &str = "callsite-ordinal";
+
 /// Create: `import { withDurableExecution } from "@cgalceran/aws-durable"`
 pub fn create_sdk_import(package_name: &str) -> ModuleItem {
     ModuleItem::ModuleDecl(ModuleDecl::Import(ImportDecl {
@@ -18,6 +27,24 @@ pub fn create_sdk_import(package_name: &str) -> ModuleItem {
     }))
 }
 
+/// Create: `import { defineWorkflow } from "@cgalceran/aws-durable"`, the client
+/// helper that makes workflow descriptors callable.
+pub fn create_client_import(package_name: &str) -> ModuleItem {
+    ModuleItem::ModuleDecl(ModuleDecl::Import(ImportDecl {
+        span: DUMMY_SP,
+        specifiers: vec![ImportSpecifier::Named(ImportNamedSpecifier {
+            span: DUMMY_SP,
+            local: ident("defineWorkflow"),
+            imported: None,
+            is_type_only: false,
+        })],
+        src: Box::new(str_lit(package_name)),
+        type_only: false,
+        with: None,
+        phase: Default::default(),
+    }))
+}
+
 /// Create: `import { LambdaClient, InvokeCommand } from "@aws-sdk/client-lambda"`
 pub fn create_lambda_sdk_import() -> ModuleItem {
     ModuleItem::ModuleDecl(ModuleDecl::Import(ImportDecl {
@@ -43,28 +70,87 @@ pub fn create_lambda_sdk_import() -> ModuleItem {
     }))
 }
 
-/// Create: `export const handler = withDurableExecution(async (event, ctx) => { ...body })`
+/// Create: `export const handler = withDurableExecution(async (event, ctx) => { ...body }, options)`
+///
+/// The workflow's own parameters are re-bound at the top of the body, see
+/// [`create_workflow_param_bindings`]. The wrapper carries the span of the
+/// original function so source maps point at the `"use workflow"` function.
+/// `binding` is the declaration's own identifier, syntax context included, so
+/// other references to the workflow in the module still resolve to it.
 pub fn create_with_durable_execution_call(
-    fn_name: &str,
+    binding: Ident,
+    params: &[Pat],
     body_stmts: Vec<Stmt>,
+    options: Option<Expr>,
     is_exported: bool,
-    is_default: bool,
+    span: Span,
 ) -> ModuleItem {
+    let decl = const_decl(
+        binding,
+        with_durable_execution(params, body_stmts, options, span),
+    );
+
+    if is_exported {
+        ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl {
+            span,
+            decl: Decl::Var(Box::new(decl)),
+        }))
+    } else {
+        ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(decl))))
+    }
+}
+
+/// Create the default export of a workflow, keeping the module's shape:
+///
+/// - anonymous: `export default withDurableExecution(async (event, ctx) => { ... })`
+/// - named: `const name = withDurableExecution(...); export default name;` so
+///   references to the local binding keep working. The const reuses the
+///   function's identifier, syntax context included, for hygiene to match.
+pub fn create_default_with_durable_execution(
+    local: Option<Ident>,
+    params: &[Pat],
+    body_stmts: Vec<Stmt>,
+    options: Option<Expr>,
+    span: Span,
+) -> Vec<ModuleItem> {
+    let call = with_durable_execution(params, body_stmts, options, span);
+    match local {
+        Some(local) => vec![
+            ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(const_decl(
+                local.clone(),
+                call,
+            ))))),
+            export_default(Expr::Ident(local)),
+        ],
+        None => vec![export_default(call)],
+    }
+}
+
+/// `withDurableExecution(async (event, ctx) => { ...bindings, ...body }, options)`
+fn with_durable_execution(
+    params: &[Pat],
+    body_stmts: Vec<Stmt>,
+    options: Option<Expr>,
+    span: Span,
+) -> Expr {
+    let mut stmts = create_workflow_param_bindings(params);
+    stmts.extend(body_stmts);
+
     let arrow = Expr::Arrow(ArrowExpr {
-        span: DUMMY_SP,
+        span,
         params: vec![
             Pat::Ident(BindingIdent {
                 id: ident("event"),
                 type_ann: None,
             }),
             Pat::Ident(BindingIdent {
-                id: ident("ctx"),
+                id: workflow_ctx_ident(params),
                 type_ann: None,
             }),
         ],
         body: Box::new(BlockStmtOrExpr::BlockStmt(BlockStmt {
             span: DUMMY_SP,
-            stmts: body_stmts,
+            stmts,
             ctxt: Default::default(),
         })),
         is_async: true,
@@ -74,47 +160,187 @@ pub fn create_with_durable_execution_call(
         ctxt: Default::default(),
     });
 
-    let call = Expr::Call(CallExpr {
-        span: DUMMY_SP,
+    let mut args = vec![ExprOrSpread::from(arrow)];
+    args.extend(options.map(ExprOrSpread::from));
+
+    Expr::Call(CallExpr {
+        span,
         callee: Callee::Expr(Box::new(Expr::Ident(ident("withDurableExecution")))),
-        args: vec![ExprOrSpread {
-            spread: None,
-            expr: Box::new(arrow),
-        }],
+        args,
         type_args: None,
         ctxt: Default::default(),
-    });
+    })
+}
 
-    let decl = VarDecl {
+/// `const name = init`
+fn const_decl(name: Ident, init: Expr) -> VarDecl {
+    VarDecl {
         span: DUMMY_SP,
         kind: VarDeclKind::Const,
         declare: false,
         decls: vec![VarDeclarator {
             span: DUMMY_SP,
             name: Pat::Ident(BindingIdent {
-                id: ident(fn_name),
+                id: name,
                 type_ann: None,
             }),
-            init: Some(Box::new(call)),
+            init: Some(Box::new(init)),
             definite: false,
         }],
         ctxt: Default::default(),
-    };
+    }
+}
 
-    if is_exported || is_default {
-        ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl {
+/// The identifier the handler's durable context is bound to.
+///
+/// A workflow that declares a plain second parameter (`async (input, durable)`)
+/// gets the context under that binding; otherwise it is called `ctx`.
+pub fn workflow_ctx_ident(params: &[Pat]) -> Ident {
+    match params.get(1) {
+        Some(Pat::Ident(binding)) => binding.id.clone(),
+        _ => ident("ctx"),
+    }
+}
+
+/// Bind a workflow's declared parameters onto the handler's `(event, ctx)`:
+///
+/// - `input` → `const input = event.input;`
+/// - `{ email } = {}` → `const { input: { email } = {} } = event;`
+/// - a destructured second parameter `{ step }` → `const { step } = ctx;`
+/// - a rest parameter `...args` → `const args = [event.input, ctx];`
+///
+/// A plain second parameter becomes the handler's context parameter itself,
+/// see [`workflow_ctx_ident`]. Parameters past the second are dropped.
+pub fn create_workflow_param_bindings(params: &[Pat]) -> Vec<Stmt> {
+    let sources = [
+        Expr::Member(MemberExpr {
             span: DUMMY_SP,
-            decl: Decl::Var(Box::new(decl)),
-        }))
-    } else {
-        ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(decl))))
+            obj: Box::new(Expr::Ident(ident("event"))),
+            prop: MemberProp::Ident(IdentName {
+                span: DUMMY_SP,
+                sym: "input".into(),
+            }),
+        }),
+        Expr::Ident(workflow_ctx_ident(params)),
+    ];
+
+    let mut stmts = vec![];
+    for (index, param) in params.iter().enumerate().take(sources.len()) {
+        let (name, init) = match param {
+            Pat::Rest(rest) => (
+                (*rest.arg).clone(),
+                Expr::Array(ArrayLit {
+                    span: DUMMY_SP,
+                    elems: sources[index..]
+                        .iter()
+                        .map(|source| {
+                            Some(ExprOrSpread {
+                                spread: None,
+                                expr: Box::new(source.clone()),
+                            })
+                        })
+                        .collect(),
+                }),
+            ),
+            Pat::Ident(_) if index == 1 => continue,
+            Pat::Ident(_) => (param.clone(), sources[index].clone()),
+            // Destructure through the event so defaults apply to `event.input`.
+            _ if index == 0 => (
+                Pat::Object(ObjectPat {
+                    span: DUMMY_SP,
+                    props: vec![ObjectPatProp::KeyValue(KeyValuePatProp {
+                        key: PropName::Ident(IdentName {
+                            span: DUMMY_SP,
+                            sym: "input".into(),
+                        }),
+                        value: Box::new(param.clone()),
+                    })],
+                    optional: false,
+                    type_ann: None,
+                }),
+                Expr::Ident(ident("event")),
+            ),
+            Pat::Assign(assign) => ((*assign.left).clone(), sources[index].clone()),
+            _ => (param.clone(), sources[index].clone()),
+        };
+
+        stmts.push(Stmt::Decl(Decl::Var(Box::new(VarDecl {
+            span: DUMMY_SP,
+            kind: VarDeclKind::Const,
+            declare: false,
+            decls: vec![VarDeclarator {
+                span: DUMMY_SP,
+                name,
+                init: Some(Box::new(init)),
+                definite: false,
+            }],
+            ctxt: Default::default(),
+        }))));
     }
+    stmts
 }
 
-/// Create: `ctx.step("name", async () => { ...body })`
-pub fn create_ctx_step_call(step_name: &str, body_stmts: Vec<Stmt>) -> Expr {
-    let arrow = Expr::Arrow(ArrowExpr {
+/// Create: `ctx.step("name", async () => { ...body })`, spanning the call site
+/// it replaces.
+pub fn create_ctx_step_call(
+    ctx: &Ident,
+    step_name: &str,
+    body_stmts: Vec<Stmt>,
+    span: Span,
+) -> Expr {
+    ctx_step_call(ctx, step_name, create_step_closure(body_stmts, span), span)
+}
+
+/// Create: `ctx.step("name", ((a, b) => async () => { ...body })(x, y))`
+///
+/// The step's parameter patterns are bound by an immediately applied arrow, so
+/// the call-site arguments are evaluated in the workflow's scope and in order,
+/// and locals declared by the inlined body stay inside the step closure. The
+/// `ctx.step` call spans the call site and the closure spans the step function,
+/// so source maps lead from either back to the user's code.
+pub fn create_bound_ctx_step_call(
+    ctx: &Ident,
+    step_name: &str,
+    params: Vec<Pat>,
+    args: Vec<ExprOrSpread>,
+    body_stmts: Vec<Stmt>,
+    call_span: Span,
+    step_span: Span,
+) -> Expr {
+    let closure = create_step_closure(body_stmts, step_span);
+    if params.is_empty() && args.is_empty() {
+        return ctx_step_call(ctx, step_name, closure, call_span);
+    }
+
+    let binder = Expr::Arrow(ArrowExpr {
+        span: step_span,
+        params,
+        body: Box::new(BlockStmtOrExpr::Expr(Box::new(closure))),
+        is_async: false,
+        is_generator: false,
+        type_params: None,
+        return_type: None,
+        ctxt: Default::default(),
+    });
+
+    let bound = Expr::Call(CallExpr {
         span: DUMMY_SP,
+        callee: Callee::Expr(Box::new(Expr::Paren(ParenExpr {
+            span: DUMMY_SP,
+            expr: Box::new(binder),
+        }))),
+        args,
+        type_args: None,
+        ctxt: Default::default(),
+    });
+
+    ctx_step_call(ctx, step_name, bound, call_span)
+}
+
+/// Create: `async () => { ...body }`
+fn create_step_closure(body_stmts: Vec<Stmt>, span: Span) -> Expr {
+    Expr::Arrow(ArrowExpr {
+        span,
         params: vec![],
         body: Box::new(BlockStmtOrExpr::BlockStmt(BlockStmt {
             span: DUMMY_SP,
@@ -126,13 +352,95 @@ pub fn create_ctx_step_call(step_name: &str, body_stmts: Vec<Stmt>) -> Expr {
         type_params: None,
         return_type: None,
         ctxt: Default::default(),
-    });
+    })
+}
+
+/// Create: `ctx.step("name", ((arg0, arg1) => () => chargeCard(arg0, arg1))(x, y))`
+///
+/// Calls a step function kept as is: one imported from another module, or a
+/// local one under the closure step strategy. As with inlined steps, the
+/// arguments are evaluated in the workflow's scope and in order; with a spread
+/// argument they are collected into one array, `((args) => () => f(...args))([x, ...ys])`.
+pub fn create_step_fn_call(
+    ctx: &Ident,
+    step_name: &str,
+    callee: Expr,
+    args: Vec<ExprOrSpread>,
+    span: Span,
+) -> Expr {
+    let arrow = |params: Vec<Pat>, body: Expr| {
+        Expr::Arrow(ArrowExpr {
+            span,
+            params,
+            body: Box::new(BlockStmtOrExpr::Expr(Box::new(body))),
+            is_async: false,
+            is_generator: false,
+            type_params: None,
+            return_type: None,
+            ctxt: Default::default(),
+        })
+    };
+    let call = |callee: Expr, args: Vec<ExprOrSpread>| {
+        Expr::Call(CallExpr {
+            span,
+            callee: Callee::Expr(Box::new(callee)),
+            args,
+            type_args: None,
+            ctxt: Default::default(),
+        })
+    };
+
+    if args.is_empty() {
+        return ctx_step_call(ctx, step_name, arrow(vec![], call(callee, vec![])), span);
+    }
+
+    let (params, forwarded, bound_args) = if args.iter().any(|arg| arg.spread.is_some()) {
+        let array = ident("args");
+        let bound = Expr::Array(ArrayLit {
+            span: DUMMY_SP,
+            elems: args.into_iter().map(Some).collect(),
+        });
+        (
+            vec![Pat::Ident(array.clone().into())],
+            vec![ExprOrSpread {
+                spread: Some(DUMMY_SP),
+                expr: Box::new(Expr::Ident(array)),
+            }],
+            vec![bound.into()],
+        )
+    } else {
+        let names: Vec<Ident> = (0..args.len()).map(|i| ident(&format!("arg{i}"))).collect();
+        (
+            names
+                .iter()
+                .map(|name| Pat::Ident(name.clone().into()))
+                .collect(),
+            names
+                .into_iter()
+                .map(|name| Expr::Ident(name).into())
+                .collect(),
+            args,
+        )
+    };
 
+    let binder = arrow(params, arrow(vec![], call(callee, forwarded)));
+    let bound = call(
+        Expr::Paren(ParenExpr {
+            span: DUMMY_SP,
+            expr: Box::new(binder),
+        }),
+        bound_args,
+    );
+    ctx_step_call(ctx, step_name, bound, span)
+}
+
+/// Create: `ctx.step("name", fn)`
+fn ctx_step_call(ctx: &Ident, step_name: &str, step_fn: Expr, span: Span) -> Expr {
     Expr::Call(CallExpr {
-        span: DUMMY_SP,
+        span,
         callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
             span: DUMMY_SP,
-            obj: Box::new(Expr::Ident(ident("ctx"))),
+            obj: Box::new(Expr::Ident(ctx.clone())),
             prop: MemberProp::Ident(IdentName {
                 span: DUMMY_SP,
                 sym: "step".into(),
@@ -145,7 +453,7 @@ pub fn create_ctx_step_call(step_name: &str, body_stmts: Vec<Stmt>) -> Expr {
             },
             ExprOrSpread {
                 spread: None,
-                expr: Box::new(arrow),
+                expr: Box::new(step_fn),
             },
         ],
         type_args: None,
@@ -153,8 +461,60 @@ pub fn create_ctx_step_call(step_name: &str, body_stmts: Vec<Stmt>) -> Expr {
     })
 }
 
-/// Create: `ctx.step("invoke:fnName", async () => { ... Lambda invocation ... })`
-pub fn create_invoke_step(fn_name_expr: &Expr, payload_expr: &Expr) -> Expr {
+/// Pass `options` as the third argument of a `ctx.step("name", fn)` call.
+pub fn with_step_options(mut step_call: Expr, options: Expr) -> Expr {
+    if let Expr::Call(call) = &mut step_call {
+        call.args.push(options.into());
+    }
+    step_call
+}
+
+/// Add `key: value` to an options bag: appended when `options` is an object
+/// literal, otherwise `{ ...options, key: value }`, or `{ key: value }` when
+/// there is none.
+pub fn with_option(options: Option<Expr>, key: &str, value: Expr) -> Expr {
+    let prop = key_value(key, value);
+    let props = match options {
+        Some(Expr::Object(mut object)) => {
+            object.props.push(prop);
+            return Expr::Object(object);
+        }
+        Some(options) => vec![
+            PropOrSpread::Spread(SpreadElement {
+                dot3_token: DUMMY_SP,
+                expr: Box::new(options),
+            }),
+            prop,
+        ],
+        None => vec![prop],
+    };
+    Expr::Object(ObjectLit {
+        span: DUMMY_SP,
+        props,
+    })
+}
+
+/// Create: `step.retry`, an option a step imported from another module
+/// carries, if any.
+pub fn create_step_option_ref(step_fn: Expr, option: &str) -> Expr {
+    Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(step_fn),
+        prop: MemberProp::Ident(IdentName {
+            span: DUMMY_SP,
+            sym: option.into(),
+        }),
+    })
+}
+
+/// Create: `ctx.step("invoke:fnName:1", async () => { ... Lambda invocation ... })`
+pub fn create_invoke_step(
+    ctx: &Ident,
+    step_id: &str,
+    fn_name_expr: &Expr,
+    payload_expr: &Expr,
+    span: Span,
+) -> Expr {
     // Build: new LambdaClient({})
     let lambda_client = Expr::New(NewExpr {
         span: DUMMY_SP,
@@ -325,18 +685,159 @@ pub fn create_invoke_step(fn_name_expr: &Expr, payload_expr: &Expr) -> Expr {
     });
 
     create_ctx_step_call(
-        "invoke",
+        ctx,
+        step_id,
         vec![client_decl, response_decl, return_stmt],
+        span,
     )
 }
 
+/// Create: `ctx.parallel("parallel:1", [(ctx) => branch, ...], { mode })`
+///
+/// Each branch receives a child context under the same binding, so the steps
+/// it runs are checkpointed under the branch's own names.
+pub fn create_ctx_parallel_call(
+    ctx: &Ident,
+    parallel_id: &str,
+    branches: Vec<Expr>,
+    mode: &str,
+    span: Span,
+) -> Expr {
+    let branches = branches
+        .into_iter()
+        .map(|branch| {
+            let arrow = Expr::Arrow(ArrowExpr {
+                span: branch.span(),
+                params: vec![Pat::Ident(ctx.clone().into())],
+                body: Box::new(BlockStmtOrExpr::Expr(Box::new(branch))),
+                is_async: false,
+                is_generator: false,
+                type_params: None,
+                return_type: None,
+                ctxt: Default::default(),
+            });
+            Some(arrow.into())
+        })
+        .collect();
+    let options = Expr::Object(ObjectLit {
+        span: DUMMY_SP,
+        props: vec![key_value("mode", Expr::Lit(Lit::Str(str_lit(mode))))],
+    });
+
+    Expr::Call(CallExpr {
+        span,
+        callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: Box::new(Expr::Ident(ctx.clone())),
+            prop: MemberProp::Ident(IdentName {
+                span: DUMMY_SP,
+                sym: "parallel".into(),
+            }),
+        }))),
+        args: vec![
+            Expr::Lit(Lit::Str(str_lit(parallel_id))).into(),
+            Expr::Array(ArrayLit {
+                span: DUMMY_SP,
+                elems: branches,
+            })
+            .into(),
+            options.into(),
+        ],
+        type_args: None,
+        ctxt: Default::default(),
+    })
+}
+
+/// Create: `ctx.map("map:processItem:1", items, mapper, opts)`
+pub fn create_ctx_map_call(
+    ctx: &Ident,
+    map_id: &str,
+    items: Expr,
+    mapper: Expr,
+    options: Option<Expr>,
+    span: Span,
+) -> Expr {
+    let mut args = vec![
+        Expr::Lit(Lit::Str(str_lit(map_id))).into(),
+        items.into(),
+        mapper.into(),
+    ];
+    args.extend(options.map(ExprOrSpread::from));
+
+    Expr::Call(CallExpr {
+        span,
+        callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: Box::new(Expr::Ident(ctx.clone())),
+            prop: MemberProp::Ident(IdentName {
+                span: DUMMY_SP,
+                sym: "map".into(),
+            }),
+        }))),
+        args,
+        type_args: None,
+        ctxt: Default::default(),
+    })
+}
+
+/// Create: `ctx.compensate("compensate:refundCard:1", ctx.step(...), undo, options)`
+pub fn create_ctx_compensate_call(
+    ctx: &Ident,
+    compensation_id: &str,
+    forward: Expr,
+    undo: Expr,
+    options: Option<Expr>,
+    span: Span,
+) -> Expr {
+    let mut args = vec![
+        Expr::Lit(Lit::Str(str_lit(compensation_id))).into(),
+        forward.into(),
+        undo.into(),
+    ];
+    args.extend(options.map(ExprOrSpread::from));
+
+    Expr::Call(CallExpr {
+        span,
+        callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: Box::new(Expr::Ident(ctx.clone())),
+            prop: MemberProp::Ident(IdentName {
+                span: DUMMY_SP,
+                sym: "compensate".into(),
+            }),
+        }))),
+        args,
+        type_args: None,
+        ctxt: Default::default(),
+    })
+}
+
+/// Create: `async (item, index) => { ...body }`, an inlined step taking its
+/// arguments directly.
+pub fn create_step_fn_expr(params: Vec<Pat>, body_stmts: Vec<Stmt>, span: Span) -> Expr {
+    Expr::Arrow(ArrowExpr {
+        span,
+        params,
+        body: Box::new(BlockStmtOrExpr::BlockStmt(BlockStmt {
+            span: DUMMY_SP,
+            stmts: body_stmts,
+            ctxt: Default::default(),
+        })),
+        is_async: true,
+        is_generator: false,
+        type_params: None,
+        return_type: None,
+        ctxt: Default::default(),
+    })
+}
+
 /// Create: `ctx.wait({ seconds: N })`
-pub fn create_ctx_wait_call(duration_expr: &Expr) -> Expr {
+pub fn create_ctx_wait_call(ctx: &Ident, duration_expr: &Expr, span: Span) -> Expr {
     Expr::Call(CallExpr {
-        span: DUMMY_SP,
+        span,
         callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
             span: DUMMY_SP,
-            obj: Box::new(Expr::Ident(ident("ctx"))),
+            obj: Box::new(Expr::Ident(ctx.clone())),
             prop: MemberProp::Ident(IdentName {
                 span: DUMMY_SP,
                 sym: "wait".into(),
@@ -352,12 +853,12 @@ pub fn create_ctx_wait_call(duration_expr: &Expr) -> Expr {
 }
 
 /// Create: `ctx.waitForCallback(name, setup, opts)`
-pub fn create_ctx_wait_for_callback_call(args: &[ExprOrSpread]) -> Expr {
+pub fn create_ctx_wait_for_callback_call(ctx: &Ident, args: &[ExprOrSpread], span: Span) -> Expr {
     Expr::Call(CallExpr {
-        span: DUMMY_SP,
+        span,
         callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
             span: DUMMY_SP,
-            obj: Box::new(Expr::Ident(ident("ctx"))),
+            obj: Box::new(Expr::Ident(ctx.clone())),
             prop: MemberProp::Ident(IdentName {
                 span: DUMMY_SP,
                 sym: "waitForCallback".into(),
@@ -369,71 +870,137 @@ pub fn create_ctx_wait_for_callback_call(args: &[ExprOrSpread]) -> Expr {
     })
 }
 
-/// Create: `export const __workflowMeta = { name: "fnName", steps: [...] }`
-pub fn create_workflow_meta_export(workflow_name: &str, step_names: &[String]) -> ModuleItem {
-    let steps_array = Expr::Array(ArrayLit {
+/// Create the metadata exports for every workflow in the module:
+///
+/// - `export const __workflowMeta = { [name]: { name, handler, params, steps, ... } }`
+/// - `export const __workflowManifest = "{\"version\":1,\"workflows\":{...}}"`
+///
+/// The manifest string is the same data as JSON, for deploy tooling that reads
+/// the build output instead of importing it.
+pub fn create_workflow_meta_exports(metas: &[WorkflowMeta]) -> Vec<ModuleItem> {
+    let meta_obj = Expr::Object(ObjectLit {
         span: DUMMY_SP,
-        elems: step_names
+        props: metas
             .iter()
-            .map(|name| {
-                Some(ExprOrSpread {
-                    spread: None,
-                    expr: Box::new(Expr::Lit(Lit::Str(str_lit(name)))),
-                })
-            })
+            .map(|meta| key_value(&meta.name, json_to_expr(&meta.to_json())))
             .collect(),
     });
 
+    let manifest = manifest_json(metas).to_string();
+    let manifest_str = Expr::Lit(Lit::Str(Str {
+        span: DUMMY_SP,
+        value: manifest.as_str().into(),
+        raw: Some(
+            serde_json::Value::String(manifest.clone())
+                .to_string()
+                .into(),
+        ),
+    }));
+
+    vec![
+        export_const("__workflowMeta", meta_obj),
+        export_const("__workflowManifest", manifest_str),
+    ]
+}
+
+/// Create: `export const __stepMeta = { [name]: { name, handler, file, params } }`
+pub fn create_step_meta_export(metas: &[StepExportMeta]) -> ModuleItem {
     let meta_obj = Expr::Object(ObjectLit {
         span: DUMMY_SP,
-        props: vec![
-            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-                key: PropName::Ident(IdentName {
-                    span: DUMMY_SP,
-                    sym: "name".into(),
-                }),
-                value: Box::new(Expr::Lit(Lit::Str(str_lit(workflow_name)))),
-            }))),
-            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-                key: PropName::Ident(IdentName {
-                    span: DUMMY_SP,
-                    sym: "steps".into(),
-                }),
-                value: Box::new(steps_array),
-            }))),
-        ],
+        props: metas
+            .iter()
+            .map(|meta| key_value(&meta.name, json_to_expr(&meta.to_json())))
+            .collect(),
     });
+    export_const("__stepMeta", meta_obj)
+}
 
-    let decl = VarDecl {
+/// Create: `export const name = init`
+fn export_const(name: &str, init: Expr) -> ModuleItem {
+    ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl {
         span: DUMMY_SP,
-        kind: VarDeclKind::Const,
-        declare: false,
-        decls: vec![VarDeclarator {
-            span: DUMMY_SP,
-            name: Pat::Ident(BindingIdent {
-                id: ident("__workflowMeta"),
-                type_ann: None,
-            }),
-            init: Some(Box::new(meta_obj)),
-            definite: false,
-        }],
-        ctxt: Default::default(),
-    };
+        decl: Decl::Var(Box::new(const_decl(ident(name), init))),
+    }))
+}
 
-    ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl {
+/// Create: `export default expr`
+fn export_default(expr: Expr) -> ModuleItem {
+    ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(ExportDefaultExpr {
         span: DUMMY_SP,
-        decl: Decl::Var(Box::new(decl)),
+        expr: Box::new(expr),
     }))
 }
 
-/// Create: `export const X = { __workflow: true, name: "X", functionName: process.env.WORKFLOW_X }`
-pub fn create_workflow_descriptor(local_name: &str, env_prefix: &str) -> ModuleItem {
-    let env_var_name = format!(
-        "{}{}",
-        env_prefix,
-        local_name.to_uppercase()
-    );
+/// Build the JS literal for a JSON value.
+fn json_to_expr(value: &serde_json::Value) -> Expr {
+    use serde_json::Value;
+
+    match value {
+        Value::Null => Expr::Lit(Lit::Null(Null { span: DUMMY_SP })),
+        Value::Bool(value) => Expr::Lit(Lit::Bool(Bool {
+            span: DUMMY_SP,
+            value: *value,
+        })),
+        Value::Number(number) => Expr::Lit(Lit::Num(Number {
+            span: DUMMY_SP,
+            value: number.as_f64().unwrap_or_default(),
+            raw: None,
+        })),
+        Value::String(value) => Expr::Lit(Lit::Str(str_lit(value))),
+        Value::Array(values) => Expr::Array(ArrayLit {
+            span: DUMMY_SP,
+            elems: values
+                .iter()
+                .map(|value| {
+                    Some(ExprOrSpread {
+                        spread: None,
+                        expr: Box::new(json_to_expr(value)),
+                    })
+                })
+                .collect(),
+        }),
+        Value::Object(entries) => Expr::Object(ObjectLit {
+            span: DUMMY_SP,
+            props: entries
+                .iter()
+                .map(|(key, value)| key_value(key, json_to_expr(value)))
+                .collect(),
+        }),
+    }
+}
+
+/// Create: `const local = defineWorkflow({ name: "X", functionName: process.env.WORKFLOW_X })`
+/// for the workflow named `X`. `local` keeps the import's syntax context so
+/// existing references still resolve to it.
+pub fn create_workflow_descriptor(
+    local: Ident,
+    workflow_name: &str,
+    env_var_name: &str,
+) -> ModuleItem {
+    binding_decl(local, workflow_descriptor(workflow_name, env_var_name))
+}
+
+/// Create: `const ns = { signupWorkflow: defineWorkflow({ ... }), ... }` for a
+/// namespace import, with one descriptor per exported workflow given as
+/// `(export name, workflow name, env var name)`.
+pub fn create_workflow_namespace(local: Ident, exports: &[(String, String, String)]) -> ModuleItem {
+    let namespace = Expr::Object(ObjectLit {
+        span: DUMMY_SP,
+        props: exports
+            .iter()
+            .map(|(export_name, workflow_name, env_var_name)| {
+                key_value(
+                    export_name,
+                    workflow_descriptor(workflow_name, env_var_name),
+                )
+            })
+            .collect(),
+    });
+    binding_decl(local, namespace)
+}
 
+/// `defineWorkflow({ name: "X", functionName: process.env.WORKFLOW_X })`
+fn workflow_descriptor(workflow_name: &str, env_var_name: &str) -> Expr {
     // process.env.WORKFLOW_X
     let env_access = Expr::Member(MemberExpr {
         span: DUMMY_SP,
@@ -454,50 +1021,78 @@ pub fn create_workflow_descriptor(local_name: &str, env_prefix: &str) -> ModuleI
     let descriptor = Expr::Object(ObjectLit {
         span: DUMMY_SP,
         props: vec![
-            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-                key: PropName::Ident(IdentName {
-                    span: DUMMY_SP,
-                    sym: "__workflow".into(),
-                }),
-                value: Box::new(Expr::Lit(Lit::Bool(Bool {
-                    span: DUMMY_SP,
-                    value: true,
-                }))),
-            }))),
-            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-                key: PropName::Ident(IdentName {
-                    span: DUMMY_SP,
-                    sym: "name".into(),
-                }),
-                value: Box::new(Expr::Lit(Lit::Str(str_lit(local_name)))),
-            }))),
-            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-                key: PropName::Ident(IdentName {
-                    span: DUMMY_SP,
-                    sym: "functionName".into(),
-                }),
-                value: Box::new(env_access),
-            }))),
+            key_value("name", Expr::Lit(Lit::Str(str_lit(workflow_name)))),
+            key_value("functionName", env_access),
         ],
     });
 
-    let decl = VarDecl {
+    Expr::Call(CallExpr {
+        span: DUMMY_SP,
+        callee: Callee::Expr(Box::new(Expr::Ident(ident("defineWorkflow")))),
+        args: vec![ExprOrSpread {
+            spread: None,
+            expr: Box::new(descriptor),
+        }],
+        type_args: None,
+        ctxt: Default::default(),
+    })
+}
+
+/// `const local = init`, keeping `local`'s syntax context.
+fn binding_decl(local: Ident, init: Expr) -> ModuleItem {
+    ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(VarDecl {
         span: DUMMY_SP,
         kind: VarDeclKind::Const,
         declare: false,
         decls: vec![VarDeclarator {
             span: DUMMY_SP,
             name: Pat::Ident(BindingIdent {
-                id: ident(local_name),
+                id: local,
                 type_ann: None,
             }),
-            init: Some(Box::new(descriptor)),
+            init: Some(Box::new(init)),
             definite: false,
         }],
         ctxt: Default::default(),
+    }))))
+}
+
+/// Env var holding the function name of `workflow_name`, e.g.
+/// `WORKFLOW_SIGNUP_WORKFLOW` for `signupWorkflow`.
+pub fn workflow_env_var_name(prefix: &str, naming: EnvNaming, workflow_name: &str) -> String {
+    let suffix = match naming {
+        EnvNaming::ScreamingSnake => screaming_snake_case(workflow_name),
+        EnvNaming::Uppercase => workflow_name.to_uppercase(),
+        EnvNaming::Preserve => workflow_name.to_string(),
     };
+    format!("{prefix}{suffix}")
+}
 
-    ModuleItem::Stmt(Stmt::Decl(Decl::Var(Box::new(decl))))
+/// `signupWorkflow` → `SIGNUP_WORKFLOW`, `HTTPHandler` → `HTTP_HANDLER`,
+/// `refund-order` → `REFUND_ORDER`.
+fn screaming_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+        if c.is_ascii_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+            let starts_word = prev.is_ascii_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_ascii_uppercase() && next_is_lower);
+            if starts_word && !out.ends_with('_') {
+                out.push('_');
+            }
+        }
+        out.push(c.to_ascii_uppercase());
+    }
+    out.trim_end_matches('_').to_string()
 }
 
 // ── Helpers ──────────────────────────────────────────────────────────
@@ -511,6 +1106,22 @@ fn ident(name: &str) -> Ident {
     }
 }
 
+/// `key: value`, quoting keys that are not identifiers.
+fn key_value(key: &str, value: Expr) -> PropOrSpread {
+    let key = if Ident::verify_symbol(key).is_ok() {
+        PropName::Ident(IdentName {
+            span: DUMMY_SP,
+            sym: key.into(),
+        })
+    } else {
+        PropName::Str(str_lit(key))
+    };
+    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+        key,
+        value: Box::new(value),
+    })))
+}
+
 fn str_lit(value: &str) -> Str {
     Str {
         span: DUMMY_SP,
@@ -518,3 +1129,17 @@ fn str_lit(value: &str) -> Str {
         raw: None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_screaming_snake_case() {
+        assert_eq!(screaming_snake_case("signupWorkflow"), "SIGNUP_WORKFLOW");
+        assert_eq!(screaming_snake_case("HTTPHandler"), "HTTP_HANDLER");
+        assert_eq!(screaming_snake_case("refund-order"), "REFUND_ORDER");
+        assert_eq!(screaming_snake_case("order2Fa"), "ORDER2_FA");
+        assert_eq!(screaming_snake_case("already_snake"), "ALREADY_SNAKE");
+    }
+}
@@ -1,30 +1,70 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use swc_core::common::{Mark, Span, Spanned, SyntaxContext};
 use swc_core::ecma::ast::*;
-use swc_core::ecma::visit::Visit;
+use swc_core::ecma::visit::{Visit, VisitWith};
 
 use crate::config::PluginConfig;
-use crate::directive::{block_has_step_directive, block_has_workflow_directive};
+use crate::diagnostics::{self, DiagnosticCode};
+use crate::directive::{
+    block_has_step_directive, block_has_workflow_directive, find_misplaced_directive,
+};
+use crate::imports::{
+    builtin_import_bindings, builtin_namespace_binding, is_step_import, is_workflow_import,
+    strip_script_extension, BUILTINS,
+};
+use crate::meta::strip_type_wrappers;
 
 /// Info about a function with `"use workflow"` directive.
 #[derive(Debug, Clone)]
 pub struct WorkflowFnInfo {
+    /// Local binding of the workflow. Anonymous default exports have none and
+    /// use their public name.
     pub name: String,
+    /// Name the workflow is known by outside the module, in metadata and
+    /// client descriptors.
+    pub public_name: String,
+    pub span: Span,
+    /// Declared parameters: the first binds to `event.input`, the second to the
+    /// durable context.
+    pub params: Vec<Pat>,
     pub is_exported: bool,
     pub is_default_export: bool,
     pub is_async: bool,
+    /// Timeout assigned next to the declaration, `signupWorkflow.timeout =
+    /// { hours: 1 }`, as written.
+    pub timeout: Option<Expr>,
 }
 
 /// Info about a function with `"use step"` directive.
 #[derive(Debug, Clone)]
 pub struct StepFnInfo {
     pub name: String,
+    /// Name workflows importing the step call it by, and the base of its step
+    /// ids there.
+    pub public_name: String,
+    pub span: Span,
+    /// Parameter patterns as declared, including defaults and rest params.
+    pub params: Vec<Pat>,
     pub body: BlockStmt,
+    /// Exported steps stay in the module as plain functions for workflows in
+    /// other modules to call.
+    pub is_exported: bool,
+    pub is_default_export: bool,
+    /// Retry policy assigned next to the declaration, `validateEmail.retry =
+    /// { maxAttempts: 3 }`, as written.
+    pub retry: Option<Expr>,
+    /// Timeout of each attempt, `validateEmail.timeout = { seconds: 30 }`, as
+    /// written.
+    pub timeout: Option<Expr>,
 }
 
 /// Info about an import from a workflow file (for client mode).
 #[derive(Debug, Clone)]
 pub struct WorkflowImportInfo {
-    pub local_name: String,
+    /// Binding the import introduces, with its syntax context.
+    pub local: Ident,
+    /// Exported name in the workflow module, `"default"` or `"*"` for a
+    /// namespace import.
     pub imported_name: String,
     pub source: String,
 }
@@ -38,10 +78,104 @@ pub struct CollectedInfo {
     pub has_invoke: bool,
     pub has_sleep: bool,
     pub has_wait_for_callback: bool,
-    /// Names of functions with step directives, so we can remove them.
-    pub step_fn_names: Vec<String>,
     /// Whether the module itself has a top-level "use workflow" directive.
     pub has_module_workflow_directive: bool,
+    /// Bindings of built-ins imported from the directives package, possibly
+    /// renamed: `import { sleep as pause }` maps `pause` to `sleep`.
+    pub builtin_imports: HashMap<Id, &'static str>,
+    /// Namespace imports of the directives package: `durable` in
+    /// `import * as durable`.
+    pub builtin_namespaces: HashSet<Id>,
+    /// Functions imported from step modules, keyed by binding, with the step
+    /// name they are checkpointed under: the name they are exported as.
+    pub step_imports: HashMap<Id, String>,
+    /// Namespace imports of step modules: `billing` in
+    /// `import * as billing from "../steps/billing"`.
+    pub step_namespaces: HashSet<Id>,
+    /// Syntax context the resolver gives identifiers with no binding. Left
+    /// empty when the module was not resolved, which matches every identifier.
+    pub unresolved_ctxt: SyntaxContext,
+}
+
+impl CollectedInfo {
+    /// The built-in `ident` refers to: a binding imported from the directives
+    /// package, or an unbound global of a built-in's name. Local functions,
+    /// parameters and other bindings that shadow a built-in name are not
+    /// built-ins.
+    pub fn builtin_name(&self, ident: &Ident) -> Option<&'static str> {
+        if let Some(builtin) = self.builtin_imports.get(&ident.to_id()) {
+            return Some(builtin);
+        }
+        if ident.ctxt != self.unresolved_ctxt {
+            return None;
+        }
+        BUILTINS.into_iter().find(|builtin| ident.sym == *builtin)
+    }
+
+    /// The built-in a call targets: `sleep(...)`, or `durable.sleep(...)` on a
+    /// namespace import of the directives package.
+    pub fn builtin_callee(&self, callee: &Callee) -> Option<&'static str> {
+        let Callee::Expr(expr) = callee else {
+            return None;
+        };
+        match expr.as_ref() {
+            Expr::Ident(ident) => self.builtin_name(ident),
+            Expr::Member(member) => {
+                let Expr::Ident(obj) = member.obj.as_ref() else {
+                    return None;
+                };
+                if !self.builtin_namespaces.contains(&obj.to_id()) {
+                    return None;
+                }
+                let prop = match &member.prop {
+                    MemberProp::Ident(prop) => prop.sym.as_str(),
+                    MemberProp::Computed(ComputedPropName { expr, .. }) => match expr.as_ref() {
+                        Expr::Lit(Lit::Str(prop)) => prop.value.as_str()?,
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                BUILTINS.into_iter().find(|builtin| *builtin == prop)
+            }
+            _ => None,
+        }
+    }
+
+    /// The step name of a call to a function imported from a step module:
+    /// `chargeCard(...)`, or `billing.chargeCard(...)` on a namespace import.
+    pub fn imported_step_callee(&self, callee: &Callee) -> Option<String> {
+        let Callee::Expr(expr) = callee else {
+            return None;
+        };
+        match expr.as_ref() {
+            Expr::Ident(ident) => self.step_imports.get(&ident.to_id()).cloned(),
+            Expr::Member(MemberExpr {
+                obj,
+                prop: MemberProp::Ident(prop),
+                ..
+            }) => match obj.as_ref() {
+                Expr::Ident(obj) if self.step_namespaces.contains(&obj.to_id()) => {
+                    Some(prop.sym.to_string())
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether the module exports steps for workflows elsewhere.
+    pub fn has_exported_steps(&self) -> bool {
+        self.step_fns.values().any(|step| step.is_exported)
+    }
+
+    fn note_builtin_call(&mut self, callee: &Callee) {
+        match self.builtin_callee(callee) {
+            Some("invoke") => self.has_invoke = true,
+            Some("sleep") => self.has_sleep = true,
+            Some("waitForCallback") => self.has_wait_for_callback = true,
+            _ => {}
+        }
+    }
 }
 
 /// Pass 1: Read-only visitor that collects workflow/step information.
@@ -62,6 +196,12 @@ impl<'a> Collector<'a> {
             current_default_export: false,
         }
     }
+
+    /// Tell built-ins from shadowing bindings using the resolver's marks.
+    pub fn with_unresolved_mark(mut self, unresolved_mark: Mark) -> Self {
+        self.info.unresolved_ctxt = SyntaxContext::empty().apply_mark(unresolved_mark);
+        self
+    }
 }
 
 impl Visit for Collector<'_> {
@@ -75,48 +215,22 @@ impl Visit for Collector<'_> {
             }
         }
 
-        // Collect imports from workflow files
+        // Collect imports from workflow files and of built-ins
         for item in items {
             if let ModuleItem::ModuleDecl(ModuleDecl::Import(import)) = item {
-                let src = import.src.value.to_string_lossy().into_owned();
-                // In client mode, any relative import could be a workflow file.
-                // We'll collect them and let the transform decide.
-                if src.starts_with("./") || src.starts_with("../") {
-                    for spec in &import.specifiers {
-                        match spec {
-                            ImportSpecifier::Named(named) => {
-                                let imported = named
-                                    .imported
-                                    .as_ref()
-                                    .map(|i| match i {
-                                        ModuleExportName::Ident(id) => id.sym.to_string(),
-                                        ModuleExportName::Str(s) => s.value.to_string_lossy().into_owned(),
-                                        _ => named.local.sym.to_string(),
-                                    })
-                                    .unwrap_or_else(|| named.local.sym.to_string());
-                                self.info.workflow_imports.push(WorkflowImportInfo {
-                                    local_name: named.local.sym.to_string(),
-                                    imported_name: imported,
-                                    source: src.clone(),
-                                });
-                            }
-                            ImportSpecifier::Default(def) => {
-                                self.info.workflow_imports.push(WorkflowImportInfo {
-                                    local_name: def.local.sym.to_string(),
-                                    imported_name: "default".to_string(),
-                                    source: src.clone(),
-                                });
-                            }
-                            ImportSpecifier::Namespace(ns) => {
-                                self.info.workflow_imports.push(WorkflowImportInfo {
-                                    local_name: ns.local.sym.to_string(),
-                                    imported_name: "*".to_string(),
-                                    source: src.clone(),
-                                });
-                            }
-                            _ => {}
-                        }
-                    }
+                if is_workflow_import(import, self.config) {
+                    self.info
+                        .workflow_imports
+                        .extend(workflow_import_specifiers(import));
+                }
+                self.info
+                    .builtin_imports
+                    .extend(builtin_import_bindings(import));
+                self.info
+                    .builtin_namespaces
+                    .extend(builtin_namespace_binding(import));
+                if is_step_import(import, self.config) {
+                    self.collect_step_import(import);
                 }
             }
         }
@@ -137,36 +251,117 @@ impl Visit for Collector<'_> {
                     self.current_export = false;
                     self.current_default_export = false;
                 }
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(export_default)) => {
+                    self.current_export = true;
+                    self.current_default_export = true;
+                    self.visit_export_default_expr(export_default);
+                    self.current_export = false;
+                    self.current_default_export = false;
+                }
                 _ => {
                     self.visit_module_item(item);
                 }
             }
         }
+
+        // Workflows declared first and exported later:
+        // `export default signupWorkflow`, `export { signupWorkflow as handler }`
+        for item in items {
+            match item {
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(export_default)) => {
+                    if let Expr::Ident(ident) = &*export_default.expr {
+                        self.mark_exported(&ident.sym, "default");
+                    }
+                }
+                ModuleItem::ModuleDecl(ModuleDecl::ExportNamed(named)) if named.src.is_none() => {
+                    for spec in &named.specifiers {
+                        let ExportSpecifier::Named(spec) = spec else {
+                            continue;
+                        };
+                        let Some(local) = module_export_name(&spec.orig) else {
+                            continue;
+                        };
+                        let exported = spec
+                            .exported
+                            .as_ref()
+                            .and_then(module_export_name)
+                            .unwrap_or_else(|| local.clone());
+                        self.mark_exported(&local, &exported);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Options assigned to steps and workflows: `validateEmail.retry = { ... }`
+        for item in items {
+            let ModuleItem::Stmt(stmt) = item else {
+                continue;
+            };
+            let Some((target, option, value)) = option_assignment(stmt) else {
+                continue;
+            };
+            let name = target.sym.as_ref();
+            if let Some(step) = self.info.step_fns.get_mut(name) {
+                match option {
+                    "retry" => {
+                        validate_retry_policy(value);
+                        step.retry = Some(value.clone());
+                    }
+                    _ => {
+                        validate_timeout(value);
+                        step.timeout = Some(value.clone());
+                    }
+                }
+            } else if let Some(workflow) = self
+                .info
+                .workflow_fns
+                .iter_mut()
+                .find(|workflow| workflow.name == name && option == "timeout")
+            {
+                validate_timeout(value);
+                workflow.timeout = Some(value.clone());
+            }
+        }
+    }
+
+    fn visit_export_default_decl(&mut self, export_default: &ExportDefaultDecl) {
+        // Handle: export default async function [name](params) { "use workflow"; ... }
+        if let DefaultDecl::Fn(fn_expr) = &export_default.decl {
+            if let Some(parts) = extract_fn_parts(&Expr::Fn(fn_expr.clone())) {
+                let name = match &fn_expr.ident {
+                    Some(ident) => ident.sym.to_string(),
+                    None => self.default_workflow_name(None),
+                };
+                self.record_fn(name, parts.span, parts.is_async, parts.params, &parts.body);
+            }
+        }
+    }
+
+    fn visit_export_default_expr(&mut self, export_default: &ExportDefaultExpr) {
+        // Handle: export default async (params) => { "use workflow"; ... }
+        if let Some(parts) = extract_fn_parts(&export_default.expr) {
+            let name = self.default_workflow_name(None);
+            self.record_fn(name, parts.span, parts.is_async, parts.params, &parts.body);
+        }
     }
 
     fn visit_fn_decl(&mut self, fn_decl: &FnDecl) {
         let name = fn_decl.ident.sym.to_string();
         if let Some(body) = &fn_decl.function.body {
-            if block_has_workflow_directive(body) {
-                self.info.workflow_fns.push(WorkflowFnInfo {
-                    name: name.clone(),
-                    is_exported: self.current_export,
-                    is_default_export: self.current_default_export,
-                    is_async: fn_decl.function.is_async,
-                });
-            }
-            if block_has_step_directive(body) {
-                self.info.step_fn_names.push(name.clone());
-                self.info.step_fns.insert(
-                    name.clone(),
-                    StepFnInfo {
-                        name,
-                        body: body.clone(),
-                    },
-                );
-            }
-            // Scan body for special calls
-            self.scan_block_for_special_calls(body);
+            let params = fn_decl
+                .function
+                .params
+                .iter()
+                .map(|p| p.pat.clone())
+                .collect();
+            self.record_fn(
+                name,
+                fn_decl.function.span,
+                fn_decl.function.is_async,
+                params,
+                body,
+            );
         }
     }
 
@@ -179,101 +374,461 @@ impl Visit for Collector<'_> {
             };
 
             if let Some(name) = name {
-                let (is_async, body) = extract_fn_body(init);
-                if let Some(body) = body {
-                    if block_has_workflow_directive(&body) {
-                        self.info.workflow_fns.push(WorkflowFnInfo {
-                            name: name.clone(),
-                            is_exported: self.current_export,
-                            is_default_export: self.current_default_export,
-                            is_async,
-                        });
-                    }
-                    if block_has_step_directive(&body) {
-                        self.info.step_fn_names.push(name.clone());
-                        self.info.step_fns.insert(
-                            name.clone(),
-                            StepFnInfo { name, body: body.clone() },
-                        );
-                    }
-                    self.scan_block_for_special_calls(&body);
+                if let Some(parts) = extract_fn_parts(init) {
+                    self.record_fn(name, parts.span, parts.is_async, parts.params, &parts.body);
                 }
             }
         }
     }
 
     fn visit_call_expr(&mut self, call: &CallExpr) {
-        if let Callee::Expr(expr) = &call.callee {
-            if let Expr::Ident(ident) = expr.as_ref() {
-                match ident.sym.as_ref() {
-                    "invoke" => self.info.has_invoke = true,
-                    "sleep" => self.info.has_sleep = true,
-                    "waitForCallback" => self.info.has_wait_for_callback = true,
-                    _ => {}
+        self.info.note_builtin_call(&call.callee);
+        // Continue into the callee too: `invoke(...).then(...)` nests the
+        // built-in call in a member chain.
+        call.visit_children_with(self);
+    }
+}
+
+impl Collector<'_> {
+    /// Public name of a default-exported workflow: the `workflowName` option,
+    /// else derived from the file name, else its local name. Client mode names
+    /// default imports after the import path the same way.
+    fn default_workflow_name(&self, local_name: Option<&str>) -> String {
+        self.config
+            .workflow_name
+            .clone()
+            .or_else(|| {
+                self.config
+                    .filename
+                    .as_deref()
+                    .and_then(workflow_name_from_path)
+            })
+            .or_else(|| local_name.map(str::to_string))
+            .unwrap_or_else(|| "workflow".to_string())
+    }
+
+    /// Public name of a default-exported step: derived from the file name as
+    /// for default imports of step modules, else its local name.
+    fn default_step_name(&self, local_name: &str) -> String {
+        self.config
+            .filename
+            .as_deref()
+            .and_then(workflow_name_from_path)
+            .unwrap_or_else(|| local_name.to_string())
+    }
+
+    /// Mark the workflow or step bound to `local` as exported under
+    /// `exported`, unless an earlier export already named it.
+    fn mark_exported(&mut self, local: &str, exported: &str) {
+        let is_default = exported == "default";
+        let public_name = if is_default {
+            self.default_workflow_name(Some(local))
+        } else {
+            exported.to_string()
+        };
+        if let Some(wf) = self
+            .info
+            .workflow_fns
+            .iter_mut()
+            .find(|w| w.name == local && !w.is_exported)
+        {
+            wf.public_name = public_name;
+            wf.is_exported = true;
+            wf.is_default_export = is_default;
+            return;
+        }
+
+        let public_name = if is_default {
+            self.default_step_name(local)
+        } else {
+            exported.to_string()
+        };
+        if let Some(step) = self
+            .info
+            .step_fns
+            .get_mut(local)
+            .filter(|step| !step.is_exported)
+        {
+            step.public_name = public_name;
+            step.is_exported = true;
+            step.is_default_export = is_default;
+        }
+    }
+
+    /// Record the bindings of an import of a step module. A default import is
+    /// named after the module path, like a default-exported step.
+    fn collect_step_import(&mut self, import: &ImportDecl) {
+        for spec in workflow_import_specifiers(import) {
+            let local = spec.local.to_id();
+            match spec.imported_name.as_str() {
+                "*" => {
+                    self.info.step_namespaces.insert(local);
                 }
+                "default" => {
+                    let name = workflow_name_from_path(&spec.source)
+                        .unwrap_or_else(|| spec.local.sym.to_string());
+                    self.info.step_imports.insert(local, name);
+                }
+                _ => {
+                    self.info.step_imports.insert(local, spec.imported_name);
+                }
+            }
+        }
+    }
+
+    /// Record a function declaration or function-valued binding, validating its
+    /// directives along the way.
+    fn record_fn(
+        &mut self,
+        name: String,
+        span: Span,
+        is_async: bool,
+        params: Vec<Pat>,
+        body: &BlockStmt,
+    ) {
+        if let Some(misplaced) = find_misplaced_directive(body) {
+            diagnostics::error(
+                misplaced.span,
+                DiagnosticCode::MisplacedDirective,
+                "directive must appear before any other statement in the function body",
+            );
+        }
+
+        let is_workflow = block_has_workflow_directive(body);
+        let is_step = block_has_step_directive(body);
+        if is_workflow && is_step {
+            diagnostics::error(
+                span,
+                DiagnosticCode::ConflictingDirectives,
+                &format!("`{name}` cannot be both a \"use workflow\" and a \"use step\" function"),
+            );
+            return;
+        }
+
+        if is_workflow {
+            if !is_async {
+                diagnostics::error(
+                    span,
+                    DiagnosticCode::WorkflowNotAsync,
+                    &format!("\"use workflow\" function `{name}` must be async"),
+                );
             }
+            let public_name = if self.current_default_export {
+                self.default_workflow_name(Some(&name))
+            } else {
+                name.clone()
+            };
+            self.info.workflow_fns.push(WorkflowFnInfo {
+                name: name.clone(),
+                public_name,
+                span,
+                params: params.clone(),
+                is_exported: self.current_export,
+                is_default_export: self.current_default_export,
+                is_async,
+                timeout: None,
+            });
         }
-        // Continue visiting children
-        for arg in &call.args {
-            self.visit_expr(&arg.expr);
+        if is_step {
+            let public_name = if self.current_default_export {
+                self.default_step_name(&name)
+            } else {
+                name.clone()
+            };
+            self.info.step_fns.insert(
+                name.clone(),
+                StepFnInfo {
+                    name,
+                    public_name,
+                    span,
+                    params,
+                    body: body.clone(),
+                    is_exported: self.current_export,
+                    is_default_export: self.current_default_export,
+                    retry: None,
+                    timeout: None,
+                },
+            );
         }
+        // Scan body for special calls
+        self.scan_block_for_special_calls(body);
     }
-}
 
-impl Collector<'_> {
     fn scan_block_for_special_calls(&mut self, block: &BlockStmt) {
-        use swc_core::ecma::visit::VisitWith;
         // Use a mini-visitor to scan for special calls within the block
-        struct CallScanner {
-            has_invoke: bool,
-            has_sleep: bool,
-            has_wait_for_callback: bool,
+        struct CallScanner<'a> {
+            info: &'a mut CollectedInfo,
         }
-        impl Visit for CallScanner {
+        impl Visit for CallScanner<'_> {
             fn visit_call_expr(&mut self, call: &CallExpr) {
-                if let Callee::Expr(expr) = &call.callee {
-                    if let Expr::Ident(ident) = expr.as_ref() {
-                        match ident.sym.as_ref() {
-                            "invoke" => self.has_invoke = true,
-                            "sleep" => self.has_sleep = true,
-                            "waitForCallback" => self.has_wait_for_callback = true,
-                            _ => {}
-                        }
-                    }
-                }
-                // Continue scanning nested calls
-                for arg in &call.args {
-                    self.visit_expr(&arg.expr);
-                }
+                self.info.note_builtin_call(&call.callee);
+                // Continue scanning nested calls, callee included
+                call.visit_children_with(self);
             }
         }
 
-        let mut scanner = CallScanner {
-            has_invoke: false,
-            has_sleep: false,
-            has_wait_for_callback: false,
+        block.visit_with(&mut CallScanner {
+            info: &mut self.info,
+        });
+    }
+}
+
+/// Options that can be assigned to a step or workflow function.
+const FN_OPTIONS: [&str; 2] = ["retry", "timeout"];
+
+/// `name.retry = policy` or `name.timeout = duration` as a module-level
+/// statement: the target binding, the option and its value.
+pub fn option_assignment(stmt: &Stmt) -> Option<(&Ident, &'static str, &Expr)> {
+    let Stmt::Expr(ExprStmt { expr, .. }) = stmt else {
+        return None;
+    };
+    let Expr::Assign(assign) = expr.as_ref() else {
+        return None;
+    };
+    if assign.op != AssignOp::Assign {
+        return None;
+    }
+    let AssignTarget::Simple(SimpleAssignTarget::Member(member)) = &assign.left else {
+        return None;
+    };
+    let (Expr::Ident(target), MemberProp::Ident(prop)) = (member.obj.as_ref(), &member.prop) else {
+        return None;
+    };
+    let option = FN_OPTIONS.into_iter().find(|option| prop.sym == *option)?;
+    Some((target, option, assign.right.as_ref()))
+}
+
+/// Options a retry policy object may set.
+const RETRY_POLICY_KEYS: [&str; 6] = [
+    "maxAttempts",
+    "backoff",
+    "initialDelay",
+    "maxDelay",
+    "jitter",
+    "retryIf",
+];
+
+/// Report misspelled options and literal values the runtime would reject.
+/// Policies that are not object literals are only known at runtime.
+fn validate_retry_policy(policy: &Expr) {
+    let Expr::Object(object) = strip_type_wrappers(policy) else {
+        return;
+    };
+    for prop in &object.props {
+        let PropOrSpread::Prop(prop) = prop else {
+            continue;
+        };
+        let (key, value) = match prop.as_ref() {
+            Prop::KeyValue(kv) => match &kv.key {
+                PropName::Ident(key) => (key.sym.as_str(), Some(kv.value.as_ref())),
+                PropName::Str(key) => match key.value.as_str() {
+                    Some(key) => (key, Some(kv.value.as_ref())),
+                    None => continue,
+                },
+                _ => continue,
+            },
+            Prop::Shorthand(ident) => (ident.sym.as_str(), None),
+            Prop::Method(method) => match &method.key {
+                PropName::Ident(key) => (key.sym.as_str(), None),
+                _ => continue,
+            },
+            _ => continue,
         };
-        block.visit_with(&mut scanner);
-        self.info.has_invoke |= scanner.has_invoke;
-        self.info.has_sleep |= scanner.has_sleep;
-        self.info.has_wait_for_callback |= scanner.has_wait_for_callback;
+        let problem = match (key, value) {
+            _ if !RETRY_POLICY_KEYS.contains(&key) => Some(format!(
+                "unknown retry option `{key}`, expected one of {}",
+                RETRY_POLICY_KEYS.join(", ")
+            )),
+            ("maxAttempts", Some(Expr::Lit(Lit::Num(n))))
+                if n.value < 1.0 || n.value.fract() != 0.0 =>
+            {
+                Some("`maxAttempts` must be a whole number of at least 1".to_string())
+            }
+            ("backoff", Some(Expr::Lit(Lit::Str(s))))
+                if !matches!(s.value.as_str(), Some("fixed" | "exponential")) =>
+            {
+                Some("`backoff` must be \"fixed\" or \"exponential\"".to_string())
+            }
+            _ => None,
+        };
+        if let Some(problem) = problem {
+            diagnostics::error(prop.span(), DiagnosticCode::InvalidRetryPolicy, &problem);
+        }
     }
 }
 
-/// Extract function body and async flag from an expression (arrow fn or fn expr).
-fn extract_fn_body(expr: &Expr) -> (bool, Option<BlockStmt>) {
-    match expr {
-        Expr::Arrow(arrow) => {
-            let body = match &*arrow.body {
-                BlockStmtOrExpr::BlockStmt(block) => Some(block.clone()),
-                _ => None,
+/// Report timeouts that are not a duration such as `{ seconds: 30 }`.
+/// Durations computed at runtime are not checked.
+fn validate_timeout(timeout: &Expr) {
+    let problem = match strip_type_wrappers(timeout) {
+        Expr::Lit(Lit::Num(_)) => Some(timeout.span()),
+        Expr::Object(object) => object.props.iter().find_map(|prop| {
+            let PropOrSpread::Prop(prop) = prop else {
+                return None;
             };
-            (arrow.is_async, body)
-        }
-        Expr::Fn(fn_expr) => {
-            let body = fn_expr.function.body.clone();
-            (fn_expr.function.is_async, body)
+            let key = match prop.as_ref() {
+                Prop::KeyValue(kv) => match &kv.key {
+                    PropName::Ident(key) => key.sym.as_str(),
+                    PropName::Str(key) => key.value.as_str()?,
+                    _ => return None,
+                },
+                Prop::Shorthand(ident) => ident.sym.as_str(),
+                _ => return None,
+            };
+            (!DURATION_UNITS.contains(&key)).then(|| prop.span())
+        }),
+        _ => None,
+    };
+    if let Some(span) = problem {
+        diagnostics::error(
+            span,
+            DiagnosticCode::InvalidTimeout,
+            "a timeout must be a duration such as `{ seconds: 30 }`, in seconds, minutes, hours or days",
+        );
+    }
+}
+
+/// Units of a duration literal.
+const DURATION_UNITS: [&str; 4] = ["seconds", "minutes", "hours", "days"];
+
+/// The value bindings of a workflow module import; type-only specifiers are
+/// skipped.
+pub fn workflow_import_specifiers(import: &ImportDecl) -> Vec<WorkflowImportInfo> {
+    let source = import.src.value.to_string_lossy().into_owned();
+    import
+        .specifiers
+        .iter()
+        .filter_map(|spec| {
+            let (local, imported_name) = match spec {
+                ImportSpecifier::Named(named) if named.is_type_only => return None,
+                ImportSpecifier::Named(named) => (
+                    &named.local,
+                    named
+                        .imported
+                        .as_ref()
+                        .and_then(module_export_name)
+                        .unwrap_or_else(|| named.local.sym.to_string()),
+                ),
+                ImportSpecifier::Default(def) => (&def.local, "default".to_string()),
+                ImportSpecifier::Namespace(ns) => (&ns.local, "*".to_string()),
+                _ => return None,
+            };
+            Some(WorkflowImportInfo {
+                local: local.clone(),
+                imported_name,
+                source: source.clone(),
+            })
+        })
+        .collect()
+}
+
+fn module_export_name(name: &ModuleExportName) -> Option<String> {
+    match name {
+        ModuleExportName::Ident(ident) => Some(ident.sym.to_string()),
+        ModuleExportName::Str(s) => Some(s.value.to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+/// Derive a workflow name from a module path: `src/workflows/signup-flow.ts`
+/// and `./workflows/signup-flow` both give `signupFlow`. An `index` module is
+/// named after its directory.
+pub fn workflow_name_from_path(path: &str) -> Option<String> {
+    let mut segments = path
+        .rsplit(['/', '\\'])
+        .filter(|s| !s.is_empty() && *s != "." && *s != "..");
+    let file = segments.next()?;
+    let mut stem = strip_script_extension(file);
+    if stem == "index" {
+        stem = segments.next()?;
+    }
+
+    let mut name = String::new();
+    let words = stem
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+        .filter(|w| !w.is_empty());
+    for (i, word) in words.enumerate() {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            if i == 0 {
+                name.push(first.to_ascii_lowercase());
+            } else {
+                name.push(first.to_ascii_uppercase());
+            }
+            name.push_str(chars.as_str());
         }
-        _ => (false, None),
+    }
+    if name.is_empty() {
+        return None;
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    Some(name)
+}
+
+/// The pieces of a function expression the collector cares about.
+struct FnParts {
+    span: Span,
+    is_async: bool,
+    params: Vec<Pat>,
+    body: BlockStmt,
+}
+
+/// Extract span, async flag, parameter patterns and block body from an
+/// expression (arrow fn or fn expr).
+fn extract_fn_parts(expr: &Expr) -> Option<FnParts> {
+    match expr {
+        Expr::Arrow(arrow) => match &*arrow.body {
+            BlockStmtOrExpr::BlockStmt(block) => Some(FnParts {
+                span: arrow.span,
+                is_async: arrow.is_async,
+                params: arrow.params.clone(),
+                body: block.clone(),
+            }),
+            _ => None,
+        },
+        Expr::Fn(fn_expr) => Some(FnParts {
+            span: fn_expr.function.span,
+            is_async: fn_expr.function.is_async,
+            params: fn_expr
+                .function
+                .params
+                .iter()
+                .map(|p| p.pat.clone())
+                .collect(),
+            body: fn_expr.function.body.clone()?,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_workflow_name_from_path() {
+        let name = |path| workflow_name_from_path(path);
+        assert_eq!(
+            name("src/workflows/signup-flow.ts").as_deref(),
+            Some("signupFlow")
+        );
+        assert_eq!(
+            name("./workflows/signup-flow").as_deref(),
+            Some("signupFlow")
+        );
+        assert_eq!(
+            name("./order.workflow.js").as_deref(),
+            Some("orderWorkflow")
+        );
+        assert_eq!(name("./order.workflow").as_deref(), Some("orderWorkflow"));
+        assert_eq!(name("src/billing/index.ts").as_deref(), Some("billing"));
+        assert_eq!(
+            name("C:\\app\\Refund_Flow.mts").as_deref(),
+            Some("refund_Flow")
+        );
+        assert_eq!(name("./2fa.ts").as_deref(), Some("_2fa"));
+        assert_eq!(name("./"), None);
     }
 }
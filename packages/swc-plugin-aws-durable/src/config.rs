@@ -1,7 +1,9 @@
+use std::collections::BTreeMap;
+
 use serde::Deserialize;
 
 #[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct PluginConfig {
     #[serde(default = "default_mode")]
     pub mode: TransformMode,
@@ -9,6 +11,34 @@ pub struct PluginConfig {
     pub package_name: String,
     #[serde(default = "default_env_prefix")]
     pub env_prefix: String,
+    /// How a workflow name becomes the env var suffix in client descriptors.
+    #[serde(default)]
+    pub env_naming: EnvNaming,
+    #[serde(default)]
+    pub determinism: DeterminismConfig,
+    /// Path of the file being transformed. Filled in from the SWC host when
+    /// not set explicitly.
+    #[serde(default)]
+    pub filename: Option<String>,
+    /// Name of a default-exported workflow. Derived from `filename` when not
+    /// set.
+    #[serde(default)]
+    pub workflow_name: Option<String>,
+    /// Globs for workflow module paths. In client mode, relative imports that
+    /// resolve to a match are replaced with descriptors.
+    #[serde(default = "default_workflow_patterns")]
+    pub workflow_patterns: Vec<String>,
+    /// Workflows built elsewhere, in the shape of `__workflowManifest`. In
+    /// client mode, imports of their files are replaced with descriptors.
+    #[serde(default)]
+    pub workflow_manifest: WorkflowManifest,
+    /// Globs for shared step module paths. In workflow mode, calls to functions
+    /// imported from a match become `ctx.step()` calls.
+    #[serde(default = "default_step_patterns")]
+    pub step_patterns: Vec<String>,
+    /// How calls to steps declared in the workflow module are emitted.
+    #[serde(default)]
+    pub step_strategy: StepStrategy,
 }
 
 impl Default for PluginConfig {
@@ -17,10 +47,95 @@ impl Default for PluginConfig {
             mode: TransformMode::Workflow,
             package_name: default_package_name(),
             env_prefix: default_env_prefix(),
+            env_naming: EnvNaming::default(),
+            determinism: DeterminismConfig::default(),
+            filename: None,
+            workflow_name: None,
+            workflow_patterns: default_workflow_patterns(),
+            workflow_manifest: WorkflowManifest::default(),
+            step_patterns: default_step_patterns(),
+            step_strategy: StepStrategy::default(),
         }
     }
 }
 
+impl PluginConfig {
+    /// Parse the plugin options. A misspelled key or invalid value panics
+    /// rather than falling back to defaults: a client file would otherwise
+    /// silently compile in workflow mode.
+    pub fn from_json(json: &str) -> Self {
+        serde_json::from_str(json)
+            .unwrap_or_else(|err| panic!("invalid aws-durable plugin config: {err}"))
+    }
+}
+
+/// Settings for the determinism linter that checks `"use workflow"` bodies.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct DeterminismConfig {
+    #[serde(default)]
+    pub level: LintLevel,
+    /// Rule names to skip, e.g. `"Date.now"` or `"process.env"`.
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Off,
+    #[default]
+    Warn,
+    Error,
+}
+
+/// The parts of a workflow manifest client mode reads. Other manifest fields
+/// are ignored.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WorkflowManifest {
+    /// Workflows keyed by name.
+    #[serde(default)]
+    pub workflows: BTreeMap<String, ManifestWorkflow>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ManifestWorkflow {
+    /// Source file, relative to the same root as `filename`.
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Export binding of the workflow, `"default"` for a default export.
+    #[serde(default)]
+    pub handler: Option<String>,
+}
+
+/// Code generated for a call to a step declared in the workflow module, shown
+/// for `await validateEmail(email)`.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum StepStrategy {
+    /// `ctx.step("validateEmail:1", ((email) => async () => { ...body })(email))`,
+    /// dropping the declaration.
+    #[default]
+    Inline,
+    /// `ctx.step("validateEmail:1", ((arg0) => () => validateEmail(arg0))(email))`,
+    /// keeping the declaration.
+    Closure,
+}
+
+/// Naming strategy for the env var holding a workflow's function name, shown
+/// for the workflow `signupWorkflow`.
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum EnvNaming {
+    /// `WORKFLOW_SIGNUP_WORKFLOW`
+    #[default]
+    ScreamingSnake,
+    /// `WORKFLOW_SIGNUPWORKFLOW`
+    Uppercase,
+    /// `WORKFLOW_signupWorkflow`
+    Preserve,
+}
+
 #[derive(Debug, Clone, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum TransformMode {
@@ -39,3 +154,19 @@ fn default_package_name() -> String {
 fn default_env_prefix() -> String {
     "WORKFLOW_".to_string()
 }
+
+fn default_workflow_patterns() -> Vec<String> {
+    vec![
+        "**/workflows/**".to_string(),
+        "**/*.workflow".to_string(),
+        "**/*.workflow.*".to_string(),
+    ]
+}
+
+fn default_step_patterns() -> Vec<String> {
+    vec![
+        "**/steps/**".to_string(),
+        "**/*.step".to_string(),
+        "**/*.step.*".to_string(),
+    ]
+}
@@ -0,0 +1,169 @@
+use swc_core::common::{Span, SyntaxContext};
+use swc_core::ecma::ast::*;
+use swc_core::ecma::visit::{Visit, VisitWith};
+
+use crate::codegen::workflow_ctx_ident;
+use crate::config::{DeterminismConfig, LintLevel};
+use crate::diagnostics::{self, DiagnosticCode};
+use crate::directive::{block_has_step_directive, block_has_workflow_directive};
+
+/// Analysis pass that flags non-deterministic calls and side-effecting I/O
+/// written directly in `"use workflow"` bodies. Workflow bodies are replayed,
+/// so anything that can differ between runs belongs in a step.
+///
+/// Step bodies and callbacks passed to `ctx.step()` on the workflow's context
+/// binding are not checked, since their results are checkpointed.
+pub struct DeterminismLinter<'a> {
+    config: &'a DeterminismConfig,
+    inside_workflow: bool,
+    /// Durable context binding of the enclosing workflow: its second
+    /// parameter, else `ctx` as in the generated handler.
+    ctx: Option<Ident>,
+}
+
+impl<'a> DeterminismLinter<'a> {
+    pub fn new(config: &'a DeterminismConfig) -> Self {
+        Self {
+            config,
+            inside_workflow: false,
+            ctx: None,
+        }
+    }
+
+    fn enter_body<F: FnOnce(&mut Self)>(
+        &mut self,
+        body: Option<&BlockStmt>,
+        params: &[Pat],
+        visit: F,
+    ) {
+        let was_inside = self.inside_workflow;
+        let outer_ctx = self.ctx.clone();
+        if let Some(body) = body {
+            if block_has_workflow_directive(body) {
+                self.inside_workflow = true;
+                self.ctx = Some(workflow_ctx_ident(params));
+            } else if block_has_step_directive(body) {
+                self.inside_workflow = false;
+            }
+        }
+        visit(self);
+        self.inside_workflow = was_inside;
+        self.ctx = outer_ctx;
+    }
+
+    /// `ctx.step` on the enclosing workflow's context binding. The default
+    /// `ctx` has no binding in the source, so it matches by name.
+    fn is_ctx_step(&self, callee: &Expr) -> bool {
+        let (Some(ctx), Expr::Member(member)) = (&self.ctx, callee) else {
+            return false;
+        };
+        let (Expr::Ident(obj), MemberProp::Ident(prop)) = (member.obj.as_ref(), &member.prop)
+        else {
+            return false;
+        };
+        prop.sym == "step"
+            && obj.sym == ctx.sym
+            && (ctx.ctxt == SyntaxContext::empty() || obj.ctxt == ctx.ctxt)
+    }
+
+    fn report(&self, span: Span, rule: &str, hint: &str) {
+        if !self.inside_workflow || self.config.allow.iter().any(|a| a == rule) {
+            return;
+        }
+        let message = format!(
+            "`{rule}` in a \"use workflow\" function {hint}; move it into a \"use step\" function"
+        );
+        match self.config.level {
+            LintLevel::Off => {}
+            LintLevel::Warn => {
+                diagnostics::warn(span, DiagnosticCode::NonDeterministicWorkflowCode, &message)
+            }
+            LintLevel::Error => {
+                diagnostics::error(span, DiagnosticCode::NonDeterministicWorkflowCode, &message)
+            }
+        }
+    }
+}
+
+impl Visit for DeterminismLinter<'_> {
+    fn visit_function(&mut self, function: &Function) {
+        let params: Vec<Pat> = function.params.iter().map(|p| p.pat.clone()).collect();
+        self.enter_body(function.body.as_ref(), &params, |this| {
+            function.visit_children_with(this)
+        });
+    }
+
+    fn visit_arrow_expr(&mut self, arrow: &ArrowExpr) {
+        let body = match &*arrow.body {
+            BlockStmtOrExpr::BlockStmt(block) => Some(block),
+            _ => None,
+        };
+        self.enter_body(body, &arrow.params, |this| arrow.visit_children_with(this));
+    }
+
+    fn visit_call_expr(&mut self, call: &CallExpr) {
+        if let Callee::Expr(callee) = &call.callee {
+            // `ctx.step(name, fn)`: the callback's result is checkpointed.
+            if self.is_ctx_step(callee) {
+                callee.visit_with(self);
+                if let Some(name) = call.args.first() {
+                    name.visit_with(self);
+                }
+                return;
+            }
+
+            match member_path(callee).as_deref() {
+                Some(rule @ ("Date.now" | "Math.random" | "performance.now")) => {
+                    self.report(call.span, rule, "returns a different value on every replay")
+                }
+                Some(rule @ ("crypto.randomUUID" | "crypto.getRandomValues")) => {
+                    self.report(call.span, rule, "returns a different value on every replay")
+                }
+                Some(rule @ ("fetch" | "setTimeout" | "setInterval")) => {
+                    self.report(call.span, rule, "performs a side effect on every replay")
+                }
+                _ => {}
+            }
+        }
+        call.visit_children_with(self);
+    }
+
+    fn visit_new_expr(&mut self, new: &NewExpr) {
+        let no_args = new.args.as_ref().is_none_or(|args| args.is_empty());
+        if no_args && member_path(&new.callee).as_deref() == Some("Date") {
+            self.report(
+                new.span,
+                "new Date",
+                "returns a different value on every replay",
+            );
+        }
+        new.visit_children_with(self);
+    }
+
+    fn visit_member_expr(&mut self, member: &MemberExpr) {
+        if member_expr_path(member).as_deref() == Some("process.env") {
+            self.report(
+                member.span,
+                "process.env",
+                "can differ between the original run and a replay",
+            );
+        }
+        member.visit_children_with(self);
+    }
+}
+
+/// Render `a.b.c` for identifier and dotted member expressions.
+fn member_path(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Ident(ident) => Some(ident.sym.to_string()),
+        Expr::Member(member) => member_expr_path(member),
+        _ => None,
+    }
+}
+
+fn member_expr_path(member: &MemberExpr) -> Option<String> {
+    match &member.prop {
+        MemberProp::Ident(prop) => Some(format!("{}.{}", member_path(&member.obj)?, prop.sym)),
+        _ => None,
+    }
+}
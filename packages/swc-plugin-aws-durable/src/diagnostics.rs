@@ -0,0 +1,60 @@
+use swc_core::common::errors::{DiagnosticId, HANDLER};
+use swc_core::common::Span;
+
+/// Stable codes for every diagnostic the plugin reports. Codes are never
+/// reused or renumbered, so they are safe to grep for and document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCode {
+    /// A `"use workflow"` function is not `async`.
+    WorkflowNotAsync,
+    /// A function carries both `"use workflow"` and `"use step"`.
+    ConflictingDirectives,
+    /// A built-in such as `invoke()` or `sleep()` is missing arguments.
+    InvalidBuiltinArguments,
+    /// A directive appears after the directive prologue, where it is a no-op.
+    MisplacedDirective,
+    /// A `"use step"` function is referenced outside of a workflow body.
+    StepOutsideWorkflow,
+    /// Non-deterministic or side-effecting code runs directly in a workflow body.
+    NonDeterministicWorkflowCode,
+    /// A namespace import of a workflow module whose exports are unknown.
+    UnresolvedWorkflowNamespace,
+    /// A step's retry policy sets an unknown option or an invalid value.
+    InvalidRetryPolicy,
+    /// A step or workflow timeout is not a duration.
+    InvalidTimeout,
+}
+
+impl DiagnosticCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiagnosticCode::WorkflowNotAsync => "AWSD001",
+            DiagnosticCode::ConflictingDirectives => "AWSD002",
+            DiagnosticCode::InvalidBuiltinArguments => "AWSD003",
+            DiagnosticCode::MisplacedDirective => "AWSD004",
+            DiagnosticCode::StepOutsideWorkflow => "AWSD005",
+            DiagnosticCode::NonDeterministicWorkflowCode => "AWSD006",
+            DiagnosticCode::UnresolvedWorkflowNamespace => "AWSD007",
+            DiagnosticCode::InvalidRetryPolicy => "AWSD008",
+            DiagnosticCode::InvalidTimeout => "AWSD009",
+        }
+    }
+}
+
+/// Report an error that fails the build, pointing at `span`.
+pub fn error(span: Span, code: DiagnosticCode, message: &str) {
+    HANDLER.with(|handler| {
+        handler
+            .struct_span_err_with_code(span, message, DiagnosticId::Error(code.as_str().into()))
+            .emit();
+    });
+}
+
+/// Report a warning pointing at `span`.
+pub fn warn(span: Span, code: DiagnosticCode, message: &str) {
+    HANDLER.with(|handler| {
+        handler
+            .struct_span_warn_with_code(span, message, DiagnosticId::Lint(code.as_str().into()))
+            .emit();
+    });
+}
@@ -12,12 +12,38 @@ pub fn is_use_step_directive(stmt: &Stmt) -> bool {
 
 /// Check if a block statement contains a `"use workflow"` directive.
 pub fn block_has_workflow_directive(block: &BlockStmt) -> bool {
-    block.stmts.iter().any(|s| is_use_workflow_directive(s))
+    block.stmts.iter().any(is_use_workflow_directive)
 }
 
 /// Check if a block statement contains a `"use step"` directive.
 pub fn block_has_step_directive(block: &BlockStmt) -> bool {
-    block.stmts.iter().any(|s| is_use_step_directive(s))
+    block.stmts.iter().any(is_use_step_directive)
+}
+
+/// Find a `"use workflow"` or `"use step"` statement that sits after the
+/// directive prologue (the leading run of string literal statements), where
+/// JavaScript no longer treats it as a directive.
+pub fn find_misplaced_directive(block: &BlockStmt) -> Option<&ExprStmt> {
+    block
+        .stmts
+        .iter()
+        .skip_while(|s| directive_value(s).is_some())
+        .find_map(|s| match s {
+            Stmt::Expr(expr_stmt) if is_use_workflow_directive(s) || is_use_step_directive(s) => {
+                Some(expr_stmt)
+            }
+            _ => None,
+        })
+}
+
+fn directive_value(stmt: &Stmt) -> Option<&Str> {
+    match stmt {
+        Stmt::Expr(ExprStmt { expr, .. }) => match expr.as_ref() {
+            Expr::Lit(Lit::Str(s)) => Some(s),
+            _ => None,
+        },
+        _ => None,
+    }
 }
 
 fn is_directive(stmt: &Stmt, value: &str) -> bool {
@@ -60,6 +86,29 @@ mod tests {
         assert!(!is_use_workflow_directive(&stmt));
     }
 
+    #[test]
+    fn finds_directive_after_prologue() {
+        let call = Stmt::Expr(ExprStmt {
+            span: DUMMY_SP,
+            expr: Box::new(Expr::Ident(Ident::new_no_ctxt("run".into(), DUMMY_SP))),
+        });
+        let prologue = BlockStmt {
+            stmts: vec![
+                make_directive("use strict"),
+                make_directive("use step"),
+                call.clone(),
+            ],
+            ..Default::default()
+        };
+        assert!(find_misplaced_directive(&prologue).is_none());
+
+        let late = BlockStmt {
+            stmts: vec![call, make_directive("use workflow")],
+            ..Default::default()
+        };
+        assert!(find_misplaced_directive(&late).is_some());
+    }
+
     #[test]
     fn ignores_other_strings() {
         let stmt = make_directive("use strict");
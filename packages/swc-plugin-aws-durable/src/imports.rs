@@ -0,0 +1,332 @@
+use std::collections::HashSet;
+use swc_core::ecma::ast::*;
+use swc_core::ecma::visit::{Visit, VisitWith};
+
+use crate::config::{ManifestWorkflow, PluginConfig};
+
+const SCRIPT_EXTENSIONS: [&str; 8] = [".ts", ".tsx", ".mts", ".cts", ".js", ".jsx", ".mjs", ".cjs"];
+
+/// Package providing the type stubs of the built-ins.
+pub const DIRECTIVES_PACKAGE: &str = "@cgalceran/aws-durable-directives";
+
+/// Functions the workflow transform rewrites into durable context calls.
+pub const BUILTINS: [&str; 5] = ["invoke", "sleep", "waitForCallback", "map", "compensate"];
+
+/// Built-ins bound by an import of the directives package, as
+/// `(local binding, built-in name)` pairs: `import { sleep as pause }` binds
+/// `pause` to `sleep`.
+pub fn builtin_import_bindings(import: &ImportDecl) -> Vec<(Id, &'static str)> {
+    if import.type_only || import.src.value != DIRECTIVES_PACKAGE {
+        return Vec::new();
+    }
+    import
+        .specifiers
+        .iter()
+        .filter_map(|spec| {
+            let ImportSpecifier::Named(named) = spec else {
+                return None;
+            };
+            if named.is_type_only {
+                return None;
+            }
+            let imported = match &named.imported {
+                Some(ModuleExportName::Ident(ident)) => ident.sym.as_str(),
+                Some(ModuleExportName::Str(s)) => s.value.as_str()?,
+                Some(_) => return None,
+                None => named.local.sym.as_str(),
+            };
+            let builtin = BUILTINS.into_iter().find(|b| *b == imported)?;
+            Some((named.local.to_id(), builtin))
+        })
+        .collect()
+}
+
+/// The binding of `import * as durable from` the directives package, whose
+/// members `durable.sleep(...)` are built-ins.
+pub fn builtin_namespace_binding(import: &ImportDecl) -> Option<Id> {
+    if import.type_only || import.src.value != DIRECTIVES_PACKAGE {
+        return None;
+    }
+    import.specifiers.iter().find_map(|spec| match spec {
+        ImportSpecifier::Namespace(ns) => Some(ns.local.to_id()),
+        _ => None,
+    })
+}
+
+/// Drop the specifiers of directives package imports that nothing refers to
+/// any more, and imports left with none. Built-in calls have been rewritten by
+/// now, so the stubs only stay where the module still uses them otherwise.
+pub fn strip_unused_directive_imports(items: &mut Vec<ModuleItem>) {
+    struct References(HashSet<Id>);
+    impl Visit for References {
+        fn visit_import_decl(&mut self, _: &ImportDecl) {}
+
+        fn visit_ident(&mut self, ident: &Ident) {
+            self.0.insert(ident.to_id());
+        }
+    }
+
+    let mut references = References(HashSet::new());
+    items.visit_with(&mut references);
+
+    items.retain_mut(|item| {
+        let ModuleItem::ModuleDecl(ModuleDecl::Import(import)) = item else {
+            return true;
+        };
+        if import.type_only
+            || import.src.value != DIRECTIVES_PACKAGE
+            || import.specifiers.is_empty()
+        {
+            return true;
+        }
+        import.specifiers.retain(|spec| {
+            let local = match spec {
+                ImportSpecifier::Named(named) => &named.local,
+                ImportSpecifier::Default(def) => &def.local,
+                ImportSpecifier::Namespace(ns) => &ns.local,
+                _ => return true,
+            };
+            references.0.contains(&local.to_id())
+        });
+        !import.specifiers.is_empty()
+    });
+}
+
+/// Whether client mode should replace `import` with workflow descriptors.
+///
+/// An import refers to a workflow module when it carries the
+/// `with { type: "workflow" }` attribute, when it resolves to a file listed in
+/// the `workflowManifest` option, or when its resolved path matches one of the
+/// `workflowPatterns` globs. Every other import is left untouched.
+pub fn is_workflow_import(import: &ImportDecl, config: &PluginConfig) -> bool {
+    if import.type_only {
+        return false;
+    }
+    if has_type_attribute(import, "workflow") {
+        return true;
+    }
+
+    let specifier = import.src.value.to_string_lossy();
+    let Some(path) = resolve_import_path(config.filename.as_deref(), &specifier) else {
+        return false;
+    };
+    let path = strip_script_extension(&path);
+
+    config
+        .workflow_manifest
+        .workflows
+        .values()
+        .any(|workflow| is_manifest_file(workflow, path))
+        || config
+            .workflow_patterns
+            .iter()
+            .any(|pattern| glob_match(pattern, path))
+}
+
+/// Whether workflow mode should treat functions of `import` as steps declared
+/// in another module.
+///
+/// An import refers to a step module when it carries the
+/// `with { type: "step" }` attribute or when its resolved path matches one of
+/// the `stepPatterns` globs.
+pub fn is_step_import(import: &ImportDecl, config: &PluginConfig) -> bool {
+    if import.type_only {
+        return false;
+    }
+    if has_type_attribute(import, "step") {
+        return true;
+    }
+
+    let specifier = import.src.value.to_string_lossy();
+    let Some(path) = resolve_import_path(config.filename.as_deref(), &specifier) else {
+        return false;
+    };
+    let path = strip_script_extension(&path);
+    config
+        .step_patterns
+        .iter()
+        .any(|pattern| glob_match(pattern, path))
+}
+
+/// Name of the workflow exported as `export_name` by the module `specifier`
+/// resolves to, when the `workflowManifest` option lists it.
+pub fn manifest_workflow_name(
+    config: &PluginConfig,
+    specifier: &str,
+    export_name: &str,
+) -> Option<String> {
+    let path = resolve_import_path(config.filename.as_deref(), specifier)?;
+    let path = strip_script_extension(&path);
+    config
+        .workflow_manifest
+        .workflows
+        .iter()
+        .find(|(_, workflow)| {
+            workflow.handler.as_deref() == Some(export_name) && is_manifest_file(workflow, path)
+        })
+        .map(|(name, _)| name.clone())
+}
+
+/// Workflows of the module `specifier` resolves to, as listed by the
+/// `workflowManifest` option: `(export name, workflow name)` pairs.
+pub fn manifest_module_exports(config: &PluginConfig, specifier: &str) -> Vec<(String, String)> {
+    let Some(path) = resolve_import_path(config.filename.as_deref(), specifier) else {
+        return Vec::new();
+    };
+    let path = strip_script_extension(&path);
+    config
+        .workflow_manifest
+        .workflows
+        .iter()
+        .filter(|(_, workflow)| is_manifest_file(workflow, path))
+        .filter_map(|(name, workflow)| Some((workflow.handler.clone()?, name.clone())))
+        .collect()
+}
+
+/// Whether a manifest workflow lives in the module at `path`, given without
+/// extension. A directory import resolves to its `index` file.
+fn is_manifest_file(workflow: &ManifestWorkflow, path: &str) -> bool {
+    workflow.file.as_deref().is_some_and(|file| {
+        let file = normalize_path(file);
+        let file = strip_script_extension(&file);
+        file == path || file.strip_suffix("/index") == Some(path)
+    })
+}
+
+/// `import { x } from "./x" with { type: "workflow" }`, for `kind` `workflow`.
+fn has_type_attribute(import: &ImportDecl, kind: &str) -> bool {
+    let Some(with) = &import.with else {
+        return false;
+    };
+    with.props.iter().any(|prop| {
+        let PropOrSpread::Prop(prop) = prop else {
+            return false;
+        };
+        let Prop::KeyValue(kv) = &**prop else {
+            return false;
+        };
+        let is_type_key = match &kv.key {
+            PropName::Ident(key) => key.sym == "type",
+            PropName::Str(key) => key.value == "type",
+            _ => false,
+        };
+        is_type_key && matches!(&*kv.value, Expr::Lit(Lit::Str(value)) if value.value == kind)
+    })
+}
+
+/// Resolve a relative import specifier against the importing file, giving a
+/// normalized path such as `src/workflows/signup`. Package imports are not
+/// resolved.
+pub fn resolve_import_path(importer: Option<&str>, specifier: &str) -> Option<String> {
+    if !(specifier.starts_with("./") || specifier.starts_with("../")) {
+        return None;
+    }
+    let dir = importer
+        .map(|importer| importer.replace('\\', "/"))
+        .and_then(|importer| importer.rsplit_once('/').map(|(dir, _)| dir.to_string()));
+    Some(match dir {
+        Some(dir) => normalize_path(&format!("{dir}/{specifier}")),
+        None => normalize_path(specifier),
+    })
+}
+
+/// Collapse `.` and `..` segments and repeated separators. A leading `/` is
+/// kept; `..` segments that climb above the start of a relative path are kept.
+fn normalize_path(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." if segments.last().is_some_and(|last| *last != "..") => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    let joined = segments.join("/");
+    if path.starts_with('/') {
+        format!("/{joined}")
+    } else {
+        joined
+    }
+}
+
+/// Drop a `.ts`, `.js`, … extension, since import specifiers usually omit it.
+pub fn strip_script_extension(path: &str) -> &str {
+    SCRIPT_EXTENSIONS
+        .iter()
+        .find_map(|ext| path.strip_suffix(ext))
+        .unwrap_or(path)
+}
+
+/// Match a path against a glob where `*` and `?` stay within one segment and
+/// `**` spans any number of segments, including none.
+fn glob_match(pattern: &str, path: &str) -> bool {
+    fn segments(s: &str) -> Vec<&str> {
+        s.split('/')
+            .filter(|s| !s.is_empty() && *s != ".")
+            .collect()
+    }
+    match_segments(&segments(pattern), &segments(path))
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path_rest)) => {
+                match_segment(first.as_bytes(), segment.as_bytes())
+                    && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &[u8], segment: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => segment.is_empty(),
+        Some((b'*', rest)) => (0..=segment.len()).any(|skip| match_segment(rest, &segment[skip..])),
+        Some((b'?', rest)) => !segment.is_empty() && match_segment(rest, &segment[1..]),
+        Some((c, rest)) => segment.first() == Some(c) && match_segment(rest, &segment[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_relative_specifiers() {
+        let resolve = resolve_import_path;
+        assert_eq!(
+            resolve(Some("src/handlers/api.ts"), "../workflows/signup").as_deref(),
+            Some("src/workflows/signup")
+        );
+        assert_eq!(
+            resolve(Some("/app/src/api.ts"), "./flows/./order").as_deref(),
+            Some("/app/src/flows/order")
+        );
+        assert_eq!(
+            resolve(None, "./workflows/a").as_deref(),
+            Some("workflows/a")
+        );
+        assert_eq!(resolve(None, "../a").as_deref(), Some("../a"));
+        assert_eq!(resolve(Some("src/api.ts"), "some-package"), None);
+    }
+
+    #[test]
+    fn matches_globs() {
+        assert!(glob_match("**/workflows/**", "workflows/a"));
+        assert!(glob_match(
+            "**/workflows/**",
+            "src/workflows/billing/refund"
+        ));
+        assert!(!glob_match("**/workflows/**", "src/utils/format"));
+        assert!(glob_match("**/*.workflow", "src/signup.workflow"));
+        assert!(glob_match("src/*/flow?", "src/billing/flow1"));
+        assert!(!glob_match("src/*/flow?", "src/billing/nested/flow1"));
+    }
+}
@@ -1,33 +1,58 @@
 pub mod codegen;
 pub mod collector;
 pub mod config;
+pub mod determinism;
+pub mod diagnostics;
 pub mod directive;
+pub mod imports;
+pub mod meta;
 pub mod transform;
 
 #[cfg(test)]
 mod tests;
 
+use swc_core::common::errors::SourceMapperDyn;
+use swc_core::common::sync::Lrc;
+use swc_core::common::Mark;
 use swc_core::ecma::ast::Program;
 use swc_core::ecma::visit::{VisitMut, VisitMutWith, VisitWith};
+use swc_core::plugin::metadata::TransformPluginMetadataContextKind;
 use swc_core::plugin::plugin_transform;
+use swc_core::transform_common::output::experimental_emit;
 
 use collector::Collector;
-use config::PluginConfig;
+use config::{LintLevel, PluginConfig, TransformMode};
+use determinism::DeterminismLinter;
 use transform::WorkflowTransformer;
 
 struct TransformPass {
     config: PluginConfig,
+    source_map: Lrc<SourceMapperDyn>,
+    unresolved_mark: Mark,
 }
 
 impl VisitMut for TransformPass {
     fn visit_mut_module(&mut self, module: &mut swc_core::ecma::ast::Module) {
         // Pass 1: Collect info
-        let mut collector = Collector::new(&self.config);
+        let mut collector = Collector::new(&self.config).with_unresolved_mark(self.unresolved_mark);
         module.visit_with(&mut collector);
 
+        if self.config.mode == TransformMode::Workflow
+            && self.config.determinism.level != LintLevel::Off
+        {
+            module.visit_with(&mut DeterminismLinter::new(&self.config.determinism));
+        }
+
         // Pass 2: Transform
-        let mut transformer = WorkflowTransformer::new(self.config.clone(), collector.info);
+        let mut transformer = WorkflowTransformer::new(self.config.clone(), collector.info)
+            .with_source_map(self.source_map.clone());
         transformer.visit_mut_module(module);
+
+        // Surfaces as `output` on the SWC transform result, so the esbuild
+        // plugin writes manifests without parsing the emitted code.
+        if let Some(manifest) = transformer.manifest() {
+            experimental_emit("workflowManifest".to_string(), manifest);
+        }
     }
 }
 
@@ -36,12 +61,19 @@ pub fn process_transform(
     mut program: Program,
     metadata: swc_core::plugin::metadata::TransformPluginProgramMetadata,
 ) -> Program {
-    let config: PluginConfig = metadata
+    let mut config: PluginConfig = metadata
         .get_transform_plugin_config()
-        .and_then(|json_str| serde_json::from_str(&json_str).ok())
+        .map(|json_str| PluginConfig::from_json(&json_str))
         .unwrap_or_default();
+    if config.filename.is_none() {
+        config.filename = metadata.get_context(&TransformPluginMetadataContextKind::Filename);
+    }
 
-    let mut pass = TransformPass { config };
+    let mut pass = TransformPass {
+        config,
+        source_map: Lrc::new(metadata.source_map.clone()),
+        unresolved_mark: metadata.unresolved_mark,
+    };
     program.visit_mut_with(&mut pass);
     program
 }
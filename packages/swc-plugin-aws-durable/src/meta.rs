@@ -0,0 +1,344 @@
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+use swc_core::ecma::ast::*;
+
+/// Manifest entry describing a single workflow, gathered while its body is
+/// rewritten. Emitted as its entry in `__workflowMeta` and, serialized, in
+/// `__workflowManifest` for deploy tooling.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowMeta {
+    pub name: String,
+    /// Export binding to use as the Lambda handler, if the workflow is exported.
+    pub handler: Option<String>,
+    pub file: Option<String>,
+    pub params: Vec<ParamMeta>,
+    /// The workflow's timeout, when it is a literal.
+    pub timeout: Option<Value>,
+    pub step_id_scheme: String,
+    /// One entry per step call site, in emission order.
+    pub steps: Vec<StepCallMeta>,
+    pub invokes: Vec<InvokeMeta>,
+    pub sleeps: Vec<SleepMeta>,
+    pub callbacks: Vec<CallbackMeta>,
+    pub parallels: Vec<ParallelMeta>,
+    pub maps: Vec<MapMeta>,
+    pub compensations: Vec<CompensationMeta>,
+}
+
+/// Entry of `__stepMeta` describing a step exported for workflows in other
+/// modules to call.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepExportMeta {
+    /// Step name importing workflows checkpoint the step under.
+    pub name: String,
+    /// Export binding of the step function.
+    pub handler: String,
+    pub file: Option<String>,
+    /// Parameter bindings as written.
+    pub params: Vec<String>,
+    /// Literal options of the step's retry policy.
+    pub retry: Option<Value>,
+    /// The step's timeout, when it is a literal.
+    pub timeout: Option<Value>,
+}
+
+/// 1-based position of a call site in the original source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParamMeta {
+    /// `"input"` for the first parameter, `"context"` for the second.
+    pub role: &'static str,
+    /// The binding as written, e.g. `input` or `{ email, name }`.
+    pub binding: String,
+    pub has_default: bool,
+    pub rest: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepCallMeta {
+    pub name: String,
+    pub id: String,
+    /// Literal options of the step's retry policy, for steps declared in the
+    /// module. Imported steps list theirs in their own `__stepMeta`.
+    pub retry: Option<Value>,
+    /// The step's timeout, when it is a literal, for steps declared in the
+    /// module.
+    pub timeout: Option<Value>,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvokeMeta {
+    /// The Lambda function name, when it is a string literal.
+    pub function_name: Option<String>,
+    pub id: String,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SleepMeta {
+    /// The duration, when it is a literal such as `{ seconds: 5 }`.
+    pub duration: Option<Value>,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallbackMeta {
+    pub name: Option<String>,
+    /// The `timeout` option, when it is a literal.
+    pub timeout: Option<Value>,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParallelMeta {
+    pub id: String,
+    /// `"all"`, `"allSettled"` or `"race"`, after the `Promise` combinator.
+    pub mode: &'static str,
+    /// Ids of the steps, invocations and maps each branch runs, in branch
+    /// order.
+    pub branches: Vec<Vec<String>>,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MapMeta {
+    /// Id of the map; element `i` is checkpointed as `<id>/<i>`.
+    pub id: String,
+    /// The step run for each element.
+    pub step: String,
+    /// The `concurrency` option, when it is a literal.
+    pub concurrency: Option<Value>,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompensationMeta {
+    /// Id the compensation is checkpointed under when it runs.
+    pub id: String,
+    /// The step undoing the work.
+    pub step: String,
+    /// Id of the step, map or parallel whose work it undoes.
+    pub compensates: String,
+    pub location: Option<Location>,
+}
+
+impl WorkflowMeta {
+    pub fn new(name: &str, step_id_scheme: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            step_id_scheme: step_id_scheme.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn record_step(
+        &mut self,
+        name: &str,
+        id: &str,
+        retry: Option<Value>,
+        timeout: Option<Value>,
+        location: Option<Location>,
+    ) {
+        self.steps.push(StepCallMeta {
+            name: name.to_string(),
+            id: id.to_string(),
+            retry,
+            timeout,
+            location,
+        });
+    }
+
+    pub fn record_params(&mut self, params: &[Pat]) {
+        self.params = params
+            .iter()
+            .zip(["input", "context"])
+            .map(|(pat, role)| ParamMeta {
+                role,
+                binding: render_pat(pat),
+                has_default: matches!(pat, Pat::Assign(_)),
+                rest: matches!(pat, Pat::Rest(_)),
+            })
+            .collect();
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("workflow metadata is serializable")
+    }
+}
+
+impl StepExportMeta {
+    pub fn new(
+        name: &str,
+        handler: &str,
+        file: Option<String>,
+        params: &[Pat],
+        retry: Option<Value>,
+        timeout: Option<Value>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            handler: handler.to_string(),
+            file,
+            params: params.iter().map(render_pat).collect(),
+            retry,
+            timeout,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("step metadata is serializable")
+    }
+}
+
+/// The full manifest for a module: `{ version, workflows: { [name]: meta } }`.
+pub fn manifest_json(metas: &[WorkflowMeta]) -> Value {
+    let workflows: Map<String, Value> = metas
+        .iter()
+        .map(|meta| (meta.name.clone(), meta.to_json()))
+        .collect();
+    let mut manifest = Map::new();
+    manifest.insert("version".into(), Value::from(1));
+    manifest.insert("workflows".into(), Value::Object(workflows));
+    Value::Object(manifest)
+}
+
+/// Evaluate an expression built only from literals (strings, numbers,
+/// booleans, null, arrays and objects with static keys).
+pub fn static_value(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Lit(Lit::Str(s)) => Some(Value::String(s.value.to_string_lossy().into_owned())),
+        Expr::Lit(Lit::Num(n)) if n.value.fract() == 0.0 && n.value.abs() < i64::MAX as f64 => {
+            Some(Value::Number(Number::from(n.value as i64)))
+        }
+        Expr::Lit(Lit::Num(n)) => Number::from_f64(n.value).map(Value::Number),
+        Expr::Lit(Lit::Bool(b)) => Some(Value::Bool(b.value)),
+        Expr::Lit(Lit::Null(_)) => Some(Value::Null),
+        Expr::Paren(paren) => static_value(&paren.expr),
+        Expr::Array(array) => array
+            .elems
+            .iter()
+            .map(|elem| match elem {
+                Some(ExprOrSpread { spread: None, expr }) => static_value(expr),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(Value::Array),
+        Expr::Object(object) => object
+            .props
+            .iter()
+            .map(|prop| match prop {
+                PropOrSpread::Prop(prop) => match prop.as_ref() {
+                    Prop::KeyValue(kv) => Some((prop_name(&kv.key)?, static_value(&kv.value)?)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<Map<_, _>>>()
+            .map(Value::Object),
+        _ => None,
+    }
+}
+
+/// The literal entries of an object literal, leaving out the ones only known at
+/// runtime, such as a `retryIf` predicate.
+pub fn static_props(expr: &Expr) -> Option<Value> {
+    match strip_type_wrappers(expr) {
+        Expr::Object(object) => Some(Value::Object(
+            object
+                .props
+                .iter()
+                .filter_map(|prop| match prop {
+                    PropOrSpread::Prop(prop) => match prop.as_ref() {
+                        Prop::KeyValue(kv) => Some((prop_name(&kv.key)?, static_value(&kv.value)?)),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// The expression inside parentheses and type assertions, e.g. the object of
+/// `({ maxAttempts: 3 } satisfies RetryPolicy)`.
+pub fn strip_type_wrappers(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Paren(paren) => strip_type_wrappers(&paren.expr),
+        Expr::TsSatisfies(satisfies) => strip_type_wrappers(&satisfies.expr),
+        Expr::TsAs(as_expr) => strip_type_wrappers(&as_expr.expr),
+        Expr::TsConstAssertion(assertion) => strip_type_wrappers(&assertion.expr),
+        _ => expr,
+    }
+}
+
+/// Look up `key` in an object literal, e.g. the `timeout` of an options bag.
+pub fn object_prop<'a>(expr: &'a Expr, key: &str) -> Option<&'a Expr> {
+    match expr {
+        Expr::Object(object) => object.props.iter().find_map(|prop| match prop {
+            PropOrSpread::Prop(prop) => match prop.as_ref() {
+                Prop::KeyValue(kv) if prop_name(&kv.key).as_deref() == Some(key) => {
+                    Some(kv.value.as_ref())
+                }
+                _ => None,
+            },
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn prop_name(key: &PropName) -> Option<String> {
+    match key {
+        PropName::Ident(ident) => Some(ident.sym.to_string()),
+        PropName::Str(s) => Some(s.value.to_string_lossy().into_owned()),
+        _ => None,
+    }
+}
+
+/// Render a binding pattern compactly: `input`, `{ email, name }`, `[a, b]`.
+fn render_pat(pat: &Pat) -> String {
+    match pat {
+        Pat::Ident(binding) => binding.id.sym.to_string(),
+        Pat::Assign(assign) => render_pat(&assign.left),
+        Pat::Rest(rest) => format!("...{}", render_pat(&rest.arg)),
+        Pat::Array(array) => {
+            let elems: Vec<String> = array
+                .elems
+                .iter()
+                .map(|elem| elem.as_ref().map(render_pat).unwrap_or_default())
+                .collect();
+            format!("[{}]", elems.join(", "))
+        }
+        Pat::Object(object) => {
+            let props: Vec<String> = object
+                .props
+                .iter()
+                .map(|prop| match prop {
+                    ObjectPatProp::Assign(assign) => assign.key.sym.to_string(),
+                    ObjectPatProp::KeyValue(kv) => prop_name(&kv.key).unwrap_or_default(),
+                    ObjectPatProp::Rest(rest) => format!("...{}", render_pat(&rest.arg)),
+                    _ => String::new(),
+                })
+                .collect();
+            format!("{{ {} }}", props.join(", "))
+        }
+        _ => String::new(),
+    }
+}
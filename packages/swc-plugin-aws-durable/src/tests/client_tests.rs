@@ -17,37 +17,91 @@ fn parse_module(code: &str) -> Module {
     );
 
     let mut errors = vec![];
-    let module = parser::parse_file_as_module(
+    parser::parse_file_as_module(
         &fm,
         Syntax::Es(EsSyntax {
             decorators: true,
+            import_attributes: true,
             ..Default::default()
         }),
         swc_core::ecma::ast::EsVersion::Es2022,
         None,
         &mut errors,
     )
-    .expect("Failed to parse");
+    .expect("Failed to parse")
+}
 
-    module
+fn print_module(module: &Module) -> String {
+    use swc_core::common::sync::Lrc;
+    use swc_core::common::SourceMap;
+    use swc_core::ecma::codegen::{text_writer::JsWriter, Emitter};
+
+    let cm: Lrc<SourceMap> = Default::default();
+    let mut buf = vec![];
+    {
+        let mut emitter = Emitter {
+            cfg: Default::default(),
+            cm: cm.clone(),
+            comments: None,
+            wr: JsWriter::new(cm, "\n", &mut buf, None),
+        };
+        emitter.emit_module(module).expect("Failed to emit");
+    }
+    String::from_utf8(buf).expect("Invalid utf8")
 }
 
 fn transform_client(code: &str) -> Module {
+    transform_client_with(
+        PluginConfig {
+            mode: TransformMode::Client,
+            ..Default::default()
+        },
+        code,
+    )
+}
+
+fn transform_client_with(config: PluginConfig, code: &str) -> Module {
+    use swc_core::common::errors::{Handler, HANDLER};
+
     let mut module = parse_module(code);
-    let config = PluginConfig {
-        mode: TransformMode::Client,
-        ..Default::default()
-    };
 
-    let mut collector = Collector::new(&config);
-    module.visit_with(&mut collector);
+    // Diagnostics are covered in diagnostics_tests
+    let handler = Handler::with_emitter_writer(Box::new(std::io::sink()), None);
+    HANDLER.set(&handler, || {
+        let mut collector = Collector::new(&config);
+        module.visit_with(&mut collector);
 
-    let mut transformer = WorkflowTransformer::new(config.clone(), collector.info);
-    transformer.visit_mut_module(&mut module);
+        let mut transformer = WorkflowTransformer::new(config.clone(), collector.info);
+        transformer.visit_mut_module(&mut module);
+    });
 
     module
 }
 
+/// The `name` passed to a `defineWorkflow({ ... })` descriptor.
+fn descriptor_name(expr: &Expr) -> Option<String> {
+    let Expr::Call(call) = expr else {
+        return None;
+    };
+    let Expr::Object(obj) = &*call.args.first()?.expr else {
+        return None;
+    };
+    obj.props.iter().find_map(|prop| {
+        let PropOrSpread::Prop(prop) = prop else {
+            return None;
+        };
+        let Prop::KeyValue(kv) = &**prop else {
+            return None;
+        };
+        match (&kv.key, &*kv.value) {
+            (PropName::Ident(key), Expr::Lit(Lit::Str(value))) if key.sym == "name" => {
+                Some(value.value.to_string_lossy().into_owned())
+            }
+            _ => None,
+        }
+    })
+}
+
 #[test]
 fn test_client_mode_replaces_imports() {
     let input = r#"
@@ -131,13 +185,12 @@ async function run() {
 
     let module = transform_client(input);
 
-    // Both workflow imports should be replaced
-    let import_count = module
-        .body
-        .iter()
-        .filter(|item| matches!(item, ModuleItem::ModuleDecl(ModuleDecl::Import(_))))
-        .count();
-    assert_eq!(import_count, 0, "All relative imports should be replaced");
+    // Both workflow imports should be replaced, leaving only the client helper
+    assert_eq!(
+        import_sources(&module),
+        ["@cgalceran/aws-durable"],
+        "All relative imports should be replaced"
+    );
 
     // Should have 2 descriptor consts + the function
     let var_count = module
@@ -147,3 +200,252 @@ async function run() {
         .count();
     assert_eq!(var_count, 2, "Should have 2 workflow descriptors");
 }
+
+#[test]
+fn test_client_mode_default_import_named_after_module() {
+    let input = r#"
+import startSignup, { orderWorkflow } from "./workflows/signup-flow";
+
+export async function handler(event) {
+    await orderWorkflow(event);
+    return startSignup(event);
+}
+"#;
+
+    let module = transform_client(input);
+
+    let descriptors: Vec<(String, String)> = module
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            ModuleItem::Stmt(Stmt::Decl(Decl::Var(var_decl))) => var_decl.decls.first(),
+            _ => None,
+        })
+        .filter_map(|d| match (&d.name, d.init.as_deref()) {
+            (Pat::Ident(ident), Some(init)) => {
+                Some((ident.id.sym.to_string(), descriptor_name(init)?))
+            }
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(
+        descriptors,
+        [
+            ("startSignup".to_string(), "signupFlow".to_string()),
+            ("orderWorkflow".to_string(), "orderWorkflow".to_string()),
+        ]
+    );
+}
+
+fn import_sources(module: &Module) -> Vec<String> {
+    module
+        .body
+        .iter()
+        .filter_map(|item| match item {
+            ModuleItem::ModuleDecl(ModuleDecl::Import(import)) => {
+                Some(import.src.value.to_string_lossy().into_owned())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_client_mode_keeps_non_workflow_relative_imports() {
+    let input = r#"
+import { formatDate } from "./utils";
+import { signupWorkflow } from "./workflows/signup";
+import { orderWorkflow } from "../order.workflow";
+
+export async function handler(event) {
+    await orderWorkflow(event);
+    return signupWorkflow({ at: formatDate(event.time) });
+}
+"#;
+
+    let module = transform_client(input);
+
+    assert_eq!(
+        import_sources(&module),
+        ["./utils", "@cgalceran/aws-durable"]
+    );
+}
+
+#[test]
+fn test_client_mode_import_attribute() {
+    let input = r#"
+import { signupWorkflow } from "./signup" with { type: "workflow" };
+import config from "./config.json" with { type: "json" };
+"#;
+
+    let module = transform_client(input);
+
+    assert_eq!(
+        import_sources(&module),
+        ["./config.json", "@cgalceran/aws-durable"]
+    );
+}
+
+#[test]
+fn test_client_mode_workflow_manifest() {
+    let input = r#"
+import { refundWorkflow } from "../billing/refund";
+import { signupWorkflow } from "./workflows/signup";
+import { formatDate } from "../billing/format";
+"#;
+
+    let config: PluginConfig = serde_json::from_value(serde_json::json!({
+        "mode": "client",
+        "filename": "src/handlers/api.ts",
+        "workflowPatterns": [],
+        "workflowManifest": {
+            "version": 1,
+            "workflows": {
+                "refundWorkflow": {
+                    "file": "src/billing/refund.ts",
+                    "handler": "refundWorkflow"
+                }
+            }
+        }
+    }))
+    .unwrap();
+    let module = transform_client_with(config, input);
+
+    assert_eq!(
+        import_sources(&module),
+        [
+            "./workflows/signup",
+            "../billing/format",
+            "@cgalceran/aws-durable"
+        ]
+    );
+}
+
+#[test]
+fn test_client_mode_descriptor_uses_imported_name() {
+    let input = r#"
+import { signupWorkflow as signup } from "./workflows/signup";
+
+export const handler = (event) => signup(event);
+"#;
+
+    let code = print_module(&transform_client(input));
+
+    assert!(code.contains("const signup = defineWorkflow({"), "{code}");
+    assert!(code.contains("name: \"signupWorkflow\""), "{code}");
+    assert!(
+        code.contains("functionName: process.env.WORKFLOW_SIGNUP_WORKFLOW"),
+        "{code}"
+    );
+}
+
+#[test]
+fn test_client_mode_env_naming() {
+    let input = r#"import { signupWorkflow } from "./workflows/signup";"#;
+
+    let env_var = |naming: &str| {
+        let config: PluginConfig = serde_json::from_value(serde_json::json!({
+            "mode": "client",
+            "envPrefix": "WF_",
+            "envNaming": naming
+        }))
+        .unwrap();
+        let code = print_module(&transform_client_with(config, input));
+        let start = code.find("process.env.").expect("Missing env var") + "process.env.".len();
+        code[start..]
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .next()
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(env_var("screamingSnake"), "WF_SIGNUP_WORKFLOW");
+    assert_eq!(env_var("uppercase"), "WF_SIGNUPWORKFLOW");
+    assert_eq!(env_var("preserve"), "WF_signupWorkflow");
+}
+
+#[test]
+fn test_client_mode_manifest_names_imports() {
+    let input = r#"
+import refund, { refundWorkflow as legacyRefund } from "../billing/refund";
+"#;
+
+    let config: PluginConfig = serde_json::from_value(serde_json::json!({
+        "mode": "client",
+        "filename": "src/handlers/api.ts",
+        "workflowManifest": {
+            "workflows": {
+                "refundOrder": { "file": "src/billing/refund.ts", "handler": "default" },
+                "refundV1": { "file": "src/billing/refund.ts", "handler": "refundWorkflow" }
+            }
+        }
+    }))
+    .unwrap();
+    let code = print_module(&transform_client_with(config, input));
+
+    assert!(
+        code.contains("const refund = defineWorkflow({\n    name: \"refundOrder\""),
+        "{code}"
+    );
+    assert!(
+        code.contains("const legacyRefund = defineWorkflow({\n    name: \"refundV1\""),
+        "{code}"
+    );
+}
+
+#[test]
+fn test_client_mode_namespace_import() {
+    let input = r#"
+import * as wf from "../workflows";
+import * as unknown from "./workflows/unknown";
+
+export const handler = (event) => wf.signupWorkflow(event);
+"#;
+
+    let config: PluginConfig = serde_json::from_value(serde_json::json!({
+        "mode": "client",
+        "filename": "src/handlers/api.ts",
+        "workflowManifest": {
+            "workflows": {
+                "signupWorkflow": { "file": "src/workflows/index.ts", "handler": "signupWorkflow" },
+                "refundOrder": { "file": "src/workflows/index.ts", "handler": "default" },
+                "otherWorkflow": { "file": "src/other.ts", "handler": "otherWorkflow" }
+            }
+        }
+    }))
+    .unwrap();
+    let module = transform_client_with(config, input);
+    let code = print_module(&module);
+
+    assert!(
+        code.contains(
+            "const wf = {\n    \"default\": defineWorkflow({\n        name: \"refundOrder\""
+        ),
+        "{code}"
+    );
+    assert!(
+        code.contains("    signupWorkflow: defineWorkflow({\n        name: \"signupWorkflow\""),
+        "{code}"
+    );
+    assert!(!code.contains("otherWorkflow"), "{code}");
+
+    // Exports of a module missing from the manifest are unknown
+    assert_eq!(
+        import_sources(&module),
+        ["./workflows/unknown", "@cgalceran/aws-durable"]
+    );
+}
+
+#[test]
+fn test_client_mode_imports_client_helper_only_for_descriptors() {
+    let input = r#"
+import { formatDate } from "./utils";
+import * as wf from "./workflows";
+"#;
+
+    // The namespace cannot be resolved, so no descriptor is produced
+    let module = transform_client(input);
+
+    assert_eq!(import_sources(&module), ["./utils", "./workflows"]);
+}
@@ -0,0 +1,30 @@
+use crate::config::{LintLevel, PluginConfig, TransformMode};
+
+#[test]
+fn test_config_parses_known_fields() {
+    let config = PluginConfig::from_json(
+        r#"{ "mode": "client", "determinism": { "level": "error", "allow": ["Date.now"] } }"#,
+    );
+
+    assert_eq!(config.mode, TransformMode::Client);
+    assert_eq!(config.determinism.level, LintLevel::Error);
+    assert_eq!(config.determinism.allow, ["Date.now"]);
+}
+
+#[test]
+#[should_panic(expected = "unknown field `workflowPattern`")]
+fn test_config_rejects_misspelled_key() {
+    PluginConfig::from_json(r#"{ "mode": "client", "workflowPattern": ["**/flows/**"] }"#);
+}
+
+#[test]
+#[should_panic(expected = "unknown field `levels`")]
+fn test_config_rejects_misspelled_nested_key() {
+    PluginConfig::from_json(r#"{ "determinism": { "levels": "error" } }"#);
+}
+
+#[test]
+#[should_panic(expected = "invalid aws-durable plugin config")]
+fn test_config_rejects_invalid_value() {
+    PluginConfig::from_json(r#"{ "mode": "client", "determinism": { "level": "warning" } }"#);
+}
@@ -0,0 +1,372 @@
+use std::sync::{Arc, Mutex};
+
+use crate::collector::Collector;
+use crate::config::{DeterminismConfig, LintLevel, PluginConfig, TransformMode};
+use crate::determinism::DeterminismLinter;
+use crate::transform::WorkflowTransformer;
+use swc_core::common::errors::{DiagnosticBuilder, DiagnosticId, Emitter, Handler, HANDLER};
+use swc_core::common::sync::Lrc;
+use swc_core::common::{FileName, SourceMap, SourceMapper};
+use swc_core::ecma::ast::*;
+use swc_core::ecma::parser::{EsSyntax, Syntax};
+use swc_core::ecma::visit::{VisitMut, VisitWith};
+
+/// A reported diagnostic: its code, level and the source text its span covers.
+#[derive(Debug)]
+struct Reported {
+    code: String,
+    is_error: bool,
+    snippet: String,
+}
+
+type Emitted = Arc<Mutex<Vec<(String, bool, swc_core::common::Span)>>>;
+
+struct CollectingEmitter(Emitted);
+
+impl Emitter for CollectingEmitter {
+    fn emit(&mut self, db: &mut DiagnosticBuilder<'_>) {
+        let code = match &db.code {
+            Some(DiagnosticId::Error(code)) | Some(DiagnosticId::Lint(code)) => code.clone(),
+            None => String::new(),
+        };
+        let span = db.span.primary_span().unwrap_or_default();
+        self.0.lock().unwrap().push((code, db.is_error(), span));
+    }
+}
+
+fn diagnose(code: &str) -> Vec<Reported> {
+    diagnose_with(
+        PluginConfig {
+            mode: TransformMode::Workflow,
+            ..Default::default()
+        },
+        code,
+    )
+}
+
+fn diagnose_with(config: PluginConfig, code: &str) -> Vec<Reported> {
+    use swc_core::ecma::parser;
+
+    let cm: Lrc<SourceMap> = Default::default();
+    let fm = cm.new_source_file(FileName::Custom("test.ts".into()).into(), code.to_string());
+
+    let mut errors = vec![];
+    let mut module = parser::parse_file_as_module(
+        &fm,
+        Syntax::Es(EsSyntax::default()),
+        EsVersion::Es2022,
+        None,
+        &mut errors,
+    )
+    .expect("Failed to parse");
+
+    let emitted = Arc::new(Mutex::new(vec![]));
+    let handler = Handler::with_emitter(true, false, Box::new(CollectingEmitter(emitted.clone())));
+
+    HANDLER.set(&handler, || {
+        let mut collector = Collector::new(&config);
+        module.visit_with(&mut collector);
+
+        if config.determinism.level != LintLevel::Off {
+            module.visit_with(&mut DeterminismLinter::new(&config.determinism));
+        }
+
+        let mut transformer = WorkflowTransformer::new(config.clone(), collector.info);
+        transformer.visit_mut_module(&mut module);
+    });
+
+    let emitted = emitted.lock().unwrap();
+    emitted
+        .iter()
+        .map(|(code, is_error, span)| Reported {
+            code: code.clone(),
+            is_error: *is_error,
+            snippet: cm.span_to_snippet(*span).unwrap_or_default(),
+        })
+        .collect()
+}
+
+fn codes(reported: &[Reported]) -> Vec<&str> {
+    reported.iter().map(|r| r.code.as_str()).collect()
+}
+
+#[test]
+fn test_valid_workflow_has_no_diagnostics() {
+    let reported = diagnose(
+        r#"
+async function validate(data) {
+    "use step";
+    return data;
+}
+
+export async function myWorkflow(input) {
+    "use workflow";
+    const data = await validate(input);
+    await sleep({ seconds: 1 });
+    return invoke("other", data);
+}
+"#,
+    );
+
+    assert!(reported.is_empty(), "Unexpected diagnostics: {reported:?}");
+}
+
+#[test]
+fn test_non_async_workflow() {
+    let reported = diagnose(
+        r#"
+export function syncWorkflow(input) {
+    "use workflow";
+    return input;
+}
+"#,
+    );
+
+    assert_eq!(codes(&reported), ["AWSD001"]);
+    assert!(reported[0].snippet.starts_with("function syncWorkflow"));
+}
+
+#[test]
+fn test_conflicting_directives() {
+    let reported = diagnose(
+        r#"
+export async function confused(input) {
+    "use workflow";
+    "use step";
+    return input;
+}
+"#,
+    );
+
+    assert_eq!(codes(&reported), ["AWSD002"]);
+}
+
+#[test]
+fn test_invoke_with_missing_payload() {
+    let reported = diagnose(
+        r#"
+export async function orchestrator(input) {
+    "use workflow";
+    return await invoke("otherFn");
+}
+"#,
+    );
+
+    assert_eq!(codes(&reported), ["AWSD003"]);
+    assert_eq!(reported[0].snippet, r#"invoke("otherFn")"#);
+}
+
+#[test]
+fn test_compensate_without_step() {
+    let reported = diagnose(
+        r#"
+import { refundCard } from "./steps/billing";
+
+export async function orderWorkflow(order) {
+    "use workflow";
+    return await compensate(stripe.charge(order), refundCard);
+}
+"#,
+    );
+
+    assert_eq!(codes(&reported), ["AWSD003"]);
+    assert_eq!(
+        reported[0].snippet,
+        "compensate(stripe.charge(order), refundCard)"
+    );
+}
+
+#[test]
+fn test_invalid_retry_policy() {
+    let reported = diagnose(
+        r#"
+async function chargeCard(order) {
+    "use step";
+    return order;
+}
+chargeCard.retry = { maxAttempts: 0, backoff: "linear", retries: 3 };
+"#,
+    );
+
+    assert_eq!(codes(&reported), ["AWSD008", "AWSD008", "AWSD008"]);
+    assert_eq!(reported[0].snippet, "maxAttempts: 0");
+    assert_eq!(reported[1].snippet, "backoff: \"linear\"");
+    assert_eq!(reported[2].snippet, "retries: 3");
+}
+
+#[test]
+fn test_invalid_timeout() {
+    let reported = diagnose(
+        r#"
+async function chargeCard(order) {
+    "use step";
+    return order;
+}
+chargeCard.timeout = 30;
+
+export async function orderWorkflow(order) {
+    "use workflow";
+    return await chargeCard(order);
+}
+orderWorkflow.timeout = { hour: 1 };
+"#,
+    );
+
+    assert_eq!(codes(&reported), ["AWSD009", "AWSD009"]);
+    assert_eq!(reported[0].snippet, "30");
+    assert_eq!(reported[1].snippet, "hour: 1");
+}
+
+#[test]
+fn test_map_without_step_function() {
+    let reported = diagnose(
+        r#"
+export async function batchWorkflow(items) {
+    "use workflow";
+    return await map(items, (item) => item.id);
+}
+"#,
+    );
+
+    assert_eq!(codes(&reported), ["AWSD003"]);
+    assert_eq!(reported[0].snippet, "map(items, (item) => item.id)");
+}
+
+#[test]
+fn test_misplaced_directive() {
+    let reported = diagnose(
+        r#"
+export async function late(input) {
+    const x = input;
+    "use workflow";
+    return x;
+}
+"#,
+    );
+
+    assert_eq!(codes(&reported), ["AWSD004"]);
+    assert_eq!(reported[0].snippet, r#""use workflow";"#);
+}
+
+#[test]
+fn test_step_referenced_outside_workflow() {
+    let reported = diagnose(
+        r#"
+async function validate(data) {
+    "use step";
+    return data;
+}
+
+export async function myWorkflow(input) {
+    "use workflow";
+    return validate(input);
+}
+
+export async function helper(input) {
+    return validate(input);
+}
+"#,
+    );
+
+    assert_eq!(codes(&reported), ["AWSD005"]);
+    assert_eq!(reported[0].snippet, "validate");
+}
+
+const NON_DETERMINISTIC_WORKFLOW: &str = r#"
+async function stamp(data) {
+    "use step";
+    return { ...data, at: Date.now(), id: crypto.randomUUID() };
+}
+
+export async function orderWorkflow(input, ctx) {
+    "use workflow";
+    const startedAt = Date.now();
+    const roll = Math.random();
+    const today = new Date();
+    const fixed = new Date(input.createdAt);
+    const region = process.env.AWS_REGION;
+    const res = await fetch("https://example.com");
+    const stamped = await stamp(input);
+    const custom = await ctx.step("custom", async () => Math.random());
+    return { startedAt, roll, today, fixed, region, res, stamped, custom };
+}
+"#;
+
+#[test]
+fn test_determinism_warns_by_default() {
+    let reported = diagnose(NON_DETERMINISTIC_WORKFLOW);
+
+    let snippets: Vec<&str> = reported.iter().map(|r| r.snippet.as_str()).collect();
+    assert_eq!(
+        snippets,
+        [
+            "Date.now()",
+            "Math.random()",
+            "new Date()",
+            "process.env",
+            r#"fetch("https://example.com")"#,
+        ]
+    );
+    assert!(reported.iter().all(|r| r.code == "AWSD006" && !r.is_error));
+}
+
+#[test]
+fn test_determinism_error_level_and_allowlist() {
+    let config = PluginConfig {
+        determinism: DeterminismConfig {
+            level: LintLevel::Error,
+            allow: vec!["process.env".into(), "fetch".into(), "new Date".into()],
+        },
+        ..Default::default()
+    };
+
+    let reported = diagnose_with(config, NON_DETERMINISTIC_WORKFLOW);
+
+    let snippets: Vec<&str> = reported.iter().map(|r| r.snippet.as_str()).collect();
+    assert_eq!(snippets, ["Date.now()", "Math.random()"]);
+    assert!(reported.iter().all(|r| r.is_error));
+}
+
+#[test]
+fn test_determinism_exempts_only_the_workflow_context_step() {
+    let reported = diagnose(
+        r#"
+export async function orderWorkflow(input, durable) {
+    "use workflow";
+    const checkpointed = await durable.step("now", async () => Date.now());
+    const tracked = await analytics.step(() => Math.random());
+    return { checkpointed, tracked };
+}
+"#,
+    );
+
+    let snippets: Vec<&str> = reported.iter().map(|r| r.snippet.as_str()).collect();
+    assert_eq!(snippets, ["Math.random()"]);
+}
+
+#[test]
+fn test_determinism_off() {
+    let config = PluginConfig {
+        determinism: DeterminismConfig {
+            level: LintLevel::Off,
+            allow: vec![],
+        },
+        ..Default::default()
+    };
+
+    assert!(diagnose_with(config, NON_DETERMINISTIC_WORKFLOW).is_empty());
+}
+
+#[test]
+fn test_unresolved_workflow_namespace() {
+    let config = PluginConfig {
+        mode: TransformMode::Client,
+        ..Default::default()
+    };
+
+    let reported = diagnose_with(config, r#"import * as wf from "./workflows";"#);
+
+    assert_eq!(codes(&reported), ["AWSD007"]);
+    assert_eq!(reported[0].snippet, "wf");
+    assert!(!reported[0].is_error);
+}
@@ -1,2 +1,4 @@
 mod workflow_tests;
 mod client_tests;
+mod diagnostics_tests;
+mod config_tests;
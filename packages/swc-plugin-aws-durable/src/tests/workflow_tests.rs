@@ -1,13 +1,16 @@
 use crate::collector::Collector;
-use crate::config::{PluginConfig, TransformMode};
+use crate::config::{PluginConfig, StepStrategy, TransformMode};
 use crate::transform::WorkflowTransformer;
+use serde_json::json;
+use swc_core::common::sync::Lrc;
+use swc_core::common::{Mark, SourceMap, Span, GLOBALS};
 use swc_core::ecma::ast::*;
 use swc_core::ecma::parser::{EsSyntax, Syntax};
-use swc_core::ecma::visit::{VisitMut, VisitWith};
+use swc_core::ecma::transforms::base::{hygiene::hygiene, resolver};
+use swc_core::ecma::visit::{Visit, VisitMut, VisitMutWith, VisitWith};
 
-fn parse_module(code: &str) -> Module {
-    use swc_core::common::sync::Lrc;
-    use swc_core::common::{FileName, SourceMap};
+fn parse_module(code: &str) -> (Lrc<SourceMap>, Module) {
+    use swc_core::common::FileName;
     use swc_core::ecma::parser;
 
     let cm: Lrc<SourceMap> = Default::default();
@@ -21,6 +24,7 @@ fn parse_module(code: &str) -> Module {
         &fm,
         Syntax::Es(EsSyntax {
             decorators: true,
+            import_attributes: true,
             ..Default::default()
         }),
         swc_core::ecma::ast::EsVersion::Es2022,
@@ -29,23 +33,101 @@ fn parse_module(code: &str) -> Module {
     )
     .expect("Failed to parse");
 
-    module
+    (cm, module)
 }
 
 fn transform_workflow(code: &str) -> Module {
-    let mut module = parse_module(code);
-    let config = PluginConfig {
-        mode: TransformMode::Workflow,
-        ..Default::default()
-    };
+    transform_workflow_with(
+        PluginConfig {
+            mode: TransformMode::Workflow,
+            ..Default::default()
+        },
+        code,
+    )
+}
 
-    let mut collector = Collector::new(&config);
-    module.visit_with(&mut collector);
+fn transform_workflow_with(config: PluginConfig, code: &str) -> Module {
+    transform_workflow_mapped(config, code).1
+}
 
-    let mut transformer = WorkflowTransformer::new(config.clone(), collector.info);
-    transformer.visit_mut_module(&mut module);
+/// Transform and keep the source map, to check where generated code points.
+fn transform_workflow_mapped(config: PluginConfig, code: &str) -> (Lrc<SourceMap>, Module) {
+    run_workflow_transform(config, code, false)
+}
+
+/// Transform, then rename bindings the way SWC does after plugins run, to
+/// check that generated declarations still match the module's references.
+fn transform_workflow_hygienic(config: PluginConfig, code: &str) -> Module {
+    run_workflow_transform(config, code, true).1
+}
+
+fn run_workflow_transform(
+    config: PluginConfig,
+    code: &str,
+    apply_hygiene: bool,
+) -> (Lrc<SourceMap>, Module) {
+    let (cm, mut module) = parse_module(code);
+
+    GLOBALS.set(&Default::default(), || {
+        let unresolved_mark = Mark::new();
+        module.visit_mut_with(&mut resolver(unresolved_mark, Mark::new(), true));
+
+        let mut collector = Collector::new(&config).with_unresolved_mark(unresolved_mark);
+        module.visit_with(&mut collector);
+
+        let mut transformer =
+            WorkflowTransformer::new(config.clone(), collector.info).with_source_map(cm.clone());
+        transformer.visit_mut_module(&mut module);
+
+        if apply_hygiene {
+            module.visit_mut_with(&mut hygiene());
+        }
+    });
+
+    (cm, module)
+}
+
+/// Parse the `__workflowManifest` JSON string exported by a transformed module.
+fn manifest(module: &Module) -> serde_json::Value {
+    let json = module
+        .body
+        .iter()
+        .find_map(|item| match item {
+            ModuleItem::ModuleDecl(ModuleDecl::ExportDecl(ExportDecl {
+                decl: Decl::Var(var_decl),
+                ..
+            })) => var_decl
+                .decls
+                .iter()
+                .find_map(|d| match (&d.name, d.init.as_deref()) {
+                    (Pat::Ident(ident), Some(Expr::Lit(Lit::Str(s))))
+                        if ident.id.sym == "__workflowManifest" =>
+                    {
+                        Some(s.value.to_string_lossy().into_owned())
+                    }
+                    _ => None,
+                }),
+            _ => None,
+        })
+        .expect("Missing __workflowManifest export");
+    serde_json::from_str(&json).expect("Manifest should be valid JSON")
+}
+
+fn print_module(module: &Module) -> String {
+    use swc_core::ecma::codegen::{text_writer::JsWriter, Emitter};
 
-    module
+    let cm: Lrc<SourceMap> = Default::default();
+    let mut buf = vec![];
+    {
+        let mut emitter = Emitter {
+            cfg: Default::default(),
+            cm: cm.clone(),
+            comments: None,
+            wr: JsWriter::new(cm, "\n", &mut buf, None),
+        };
+        emitter.emit_module(module).expect("Failed to emit");
+    }
+    String::from_utf8(buf).expect("Invalid utf8")
 }
 
 fn has_import(module: &Module, source: &str) -> bool {
@@ -163,6 +245,21 @@ export async function orchestrator(input) {
     assert!(has_import(&module, "@aws-sdk/client-lambda"));
 }
 
+#[test]
+fn test_invoke_in_callee_generates_lambda_import() {
+    let input = r#"
+export async function orchestrator(input) {
+    "use workflow";
+    const result = await invoke("otherFn", input).then((x) => x);
+    return result;
+}
+"#;
+
+    let module = transform_workflow(input);
+
+    assert!(has_import(&module, "@aws-sdk/client-lambda"));
+}
+
 #[test]
 fn test_sleep_transform() {
     let input = r#"
@@ -179,3 +276,1235 @@ export async function delayedWorkflow(input) {
     assert!(has_import(&module, "@cgalceran/aws-durable"));
     assert!(!has_import(&module, "@aws-sdk/client-lambda"));
 }
+
+#[test]
+fn test_step_params_bound_to_call_args() {
+    let input = r#"
+async function validateEmail(email) {
+    "use step";
+    return email.toLowerCase();
+}
+
+async function save({ id, tags = [] }, retries = 3, ...rest) {
+    "use step";
+    return [id, tags, retries, rest];
+}
+
+export async function signupWorkflow(input) {
+    "use workflow";
+    const email = await validateEmail(input.email);
+    await save(input, undefined, 1, 2);
+    return email;
+}
+"#;
+
+    let code = print_module(&transform_workflow(input));
+
+    assert!(
+        code.contains("((email)=>async ()=>{"),
+        "Step params should be bound around the step closure:\n{code}"
+    );
+    assert!(code.contains("})(input.email)"), "Call-site args should be applied:\n{code}");
+    assert!(
+        code.contains("(({ id, tags = [] }, retries = 3, ...rest)=>async ()=>{"),
+        "Destructuring, defaults and rest params should be preserved:\n{code}"
+    );
+    assert!(code.contains("})(input, undefined, 1, 2)"));
+}
+
+#[test]
+fn test_workflow_params_bound_to_event_input() {
+    let input = r#"
+export async function signupWorkflow(input) {
+    "use workflow";
+    return input.email;
+}
+
+export const destructured = async ({ email, name = "anon" } = {}) => {
+    "use workflow";
+    return { email, name };
+};
+"#;
+
+    let code = print_module(&transform_workflow(input));
+
+    assert!(
+        code.contains("const input = event.input;"),
+        "Identifier param should bind to event.input:\n{code}"
+    );
+    assert!(
+        code.contains(r#"const { input: { email, name = "anon" } = {} } = event;"#),
+        "Destructured param with default should bind through event:\n{code}"
+    );
+}
+
+#[test]
+fn test_workflow_second_param_receives_ctx() {
+    let input = r#"
+async function validate(data) {
+    "use step";
+    return data;
+}
+
+export async function advancedWorkflow(input, durable) {
+    "use workflow";
+    const data = await validate(input);
+    await sleep({ seconds: 1 });
+    return durable.step("custom", async () => data);
+}
+"#;
+
+    let code = print_module(&transform_workflow(input));
+
+    assert!(
+        code.contains("withDurableExecution(async (event, durable)=>{"),
+        "Second param should name the context:\n{code}"
+    );
+    assert!(code.contains(r#"durable.step("validate:1""#));
+    assert!(code.contains("durable.wait({"));
+}
+
+#[test]
+fn test_control_flow_is_transformed() {
+    let input = r#"
+async function check(x) {
+    "use step";
+    return x > 1;
+}
+
+export async function branchingWorkflow(input) {
+    "use workflow";
+    if (await check(input.a)) {
+        await sleep({ seconds: 1 });
+    } else {
+        await invoke("other", {});
+    }
+    for (const item of input.items) {
+        await check(item);
+    }
+    while (input.retry) {
+        await check(0);
+    }
+    try {
+        await check(1);
+    } catch (e) {
+        await sleep({ seconds: 2 });
+    } finally {
+        await check(2);
+    }
+    switch (input.kind) {
+        case "a":
+            await check(3);
+            break;
+    }
+    const [a, b] = await Promise.all([check(4), check(5)]);
+    const msg = `${await check(6)}`;
+    const r = input.flag ? await check(7) : { v: await check(8) };
+    outer: {
+        await check(9);
+    }
+    return { a, b, msg, r };
+}
+"#;
+
+    let code = print_module(&transform_workflow(input));
+
+    assert!(!code.contains("check("), "Every step call should be rewritten:\n{code}");
+    assert!(!code.contains("sleep("), "Every sleep should be rewritten:\n{code}");
+    assert!(!code.contains("invoke("), "Every invoke should be rewritten:\n{code}");
+    assert_eq!(code.matches(r#"ctx.step("check:"#).count(), 12);
+    assert_eq!(code.matches("ctx.wait(").count(), 2);
+}
+
+#[test]
+fn test_repeated_calls_get_unique_step_ids() {
+    let input = r#"
+async function charge(amount) {
+    "use step";
+    return amount;
+}
+
+export async function billingWorkflow(input) {
+    "use workflow";
+    await charge(input.first);
+    await charge(input.second);
+    await invoke("notify", {});
+    await invoke("notify", {});
+    await invoke(input.target, {});
+    for (const item of input.items) {
+        await charge(item);
+    }
+}
+"#;
+
+    let code = print_module(&transform_workflow(input));
+
+    for id in [
+        "charge:1",
+        "charge:2",
+        "charge:3",
+        "invoke:notify:1",
+        "invoke:notify:2",
+        "invoke:1",
+    ] {
+        assert!(
+            code.contains(&format!("ctx.step(\"{id}\"")),
+            "Missing step id {id}:\n{code}"
+        );
+    }
+    assert!(code.contains(r#"stepIdScheme: "callsite-ordinal""#));
+}
+
+#[test]
+fn test_per_workflow_metadata() {
+    let input = r#"
+async function validate(data) {
+    "use step";
+    return data;
+}
+
+async function charge(order) {
+    "use step";
+    return order;
+}
+
+export async function signupWorkflow(input) {
+    "use workflow";
+    await validate(input);
+    await sleep({ seconds: 5 });
+    return invoke("sendWelcomeEmail", input);
+}
+
+export async function orderWorkflow(order) {
+    "use workflow";
+    await charge(order);
+    await charge(order);
+    return waitForCallback("approval");
+}
+"#;
+
+    let manifest = manifest(&transform_workflow(input));
+    let signup = &manifest["workflows"]["signupWorkflow"];
+    let order = &manifest["workflows"]["orderWorkflow"];
+
+    let step_ids = |wf: &serde_json::Value| -> Vec<String> {
+        wf["steps"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["id"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(step_ids(signup), ["validate:1"]);
+    assert_eq!(signup["invokes"][0]["functionName"], "sendWelcomeEmail");
+    assert_eq!(signup["sleeps"].as_array().unwrap().len(), 1);
+    assert_eq!(signup["callbacks"], json!([]));
+
+    assert_eq!(step_ids(order), ["charge:1", "charge:2"]);
+    assert_eq!(order["invokes"], json!([]));
+    assert_eq!(order["sleeps"], json!([]));
+    assert_eq!(order["callbacks"][0]["name"], "approval");
+}
+
+#[test]
+fn test_workflow_manifest() {
+    let input = r#"async function validateEmail(email) {
+    "use step";
+    return email;
+}
+
+export async function signupWorkflow({ email, name } = {}, ctx) {
+    "use workflow";
+    const valid = await validateEmail(email);
+    await sleep({ seconds: 5 });
+    await invoke("sendWelcomeEmail", { email: valid, name });
+    await invoke(process.env.TARGET, {});
+    return waitForCallback("approval", undefined, { timeout: { hours: 24 } });
+}
+"#;
+
+    let config = PluginConfig {
+        filename: Some("src/workflows/signup.ts".into()),
+        ..Default::default()
+    };
+    let module = transform_workflow_with(config, input);
+
+    assert_eq!(
+        manifest(&module),
+        json!({
+            "version": 1,
+            "workflows": {
+                "signupWorkflow": {
+                    "name": "signupWorkflow",
+                    "handler": "signupWorkflow",
+                    "file": "src/workflows/signup.ts",
+                    "params": [
+                        { "role": "input", "binding": "{ email, name }", "hasDefault": true, "rest": false },
+                        { "role": "context", "binding": "ctx", "hasDefault": false, "rest": false }
+                    ],
+                    "timeout": null,
+                    "stepIdScheme": "callsite-ordinal",
+                    "steps": [
+                        { "name": "validateEmail", "id": "validateEmail:1", "retry": null, "timeout": null, "location": { "line": 8, "column": 25 } }
+                    ],
+                    "invokes": [
+                        { "functionName": "sendWelcomeEmail", "id": "invoke:sendWelcomeEmail:1", "location": { "line": 10, "column": 11 } },
+                        { "functionName": null, "id": "invoke:1", "location": { "line": 11, "column": 11 } }
+                    ],
+                    "sleeps": [
+                        { "duration": { "seconds": 5 }, "location": { "line": 9, "column": 11 } }
+                    ],
+                    "callbacks": [
+                        { "name": "approval", "timeout": { "hours": 24 }, "location": { "line": 12, "column": 12 } }
+                    ],
+                    "parallels": [],
+                    "maps": [],
+                    "compensations": []
+                }
+            }
+        })
+    );
+
+    // The same data is exported as a plain object keyed by workflow name
+    let code = print_module(&module);
+    assert!(code.contains("export const __workflowMeta = {\n    signupWorkflow: {"));
+}
+
+#[test]
+fn test_manifest_available_from_transformer() {
+    let input = r#"
+export async function orderWorkflow(input) {
+    "use workflow";
+    await sleep({ seconds: 5 });
+    return input;
+}
+"#;
+
+    let config = PluginConfig::default();
+    let (_, mut module) = parse_module(input);
+    let mut transformer = WorkflowTransformer::new(config.clone(), {
+        let mut collector = Collector::new(&config);
+        module.visit_with(&mut collector);
+        collector.info
+    });
+    transformer.visit_mut_module(&mut module);
+
+    let emitted: serde_json::Value =
+        serde_json::from_str(&transformer.manifest().expect("Missing manifest")).unwrap();
+    assert_eq!(emitted, manifest(&module));
+}
+
+#[test]
+fn test_anonymous_default_export_stays_default() {
+    let input = r#"
+export default async (input) => {
+    "use workflow";
+    await sleep({ seconds: 1 });
+    return input;
+};
+"#;
+
+    let config = PluginConfig {
+        filename: Some("src/workflows/signup-flow.ts".into()),
+        ..Default::default()
+    };
+    let module = transform_workflow_with(config, input);
+    let code = print_module(&module);
+
+    assert!(
+        code.contains("export default withDurableExecution(async (event, ctx)=>{"),
+        "{code}"
+    );
+    let manifest = manifest(&module);
+    let workflow = &manifest["workflows"]["signupFlow"];
+    assert_eq!(workflow["name"], "signupFlow");
+    assert_eq!(workflow["handler"], "default");
+}
+
+#[test]
+fn test_named_default_export_keeps_local_binding() {
+    let input = r#"
+export default async function signup(input) {
+    "use workflow";
+    return input;
+}
+
+export const handlers = [signup];
+"#;
+
+    let config = PluginConfig {
+        workflow_name: Some("userSignup".into()),
+        filename: Some("src/workflows/signup.ts".into()),
+        ..Default::default()
+    };
+    let module = transform_workflow_hygienic(config, input);
+    let code = print_module(&module);
+
+    assert!(
+        code.contains("const signup = withDurableExecution("),
+        "{code}"
+    );
+    assert!(code.contains("export default signup;"), "{code}");
+    assert!(!code.contains("export const signup"), "{code}");
+    // Hygiene leaves the const and the other reference bound to one name
+    assert!(!code.contains("signup1"), "{code}");
+
+    let manifest = manifest(&module);
+    assert_eq!(manifest["workflows"]["userSignup"]["handler"], "default");
+}
+
+#[test]
+fn test_default_export_of_declared_workflow() {
+    let input = r#"
+async function signupWorkflow(input) {
+    "use workflow";
+    return input;
+}
+
+export default signupWorkflow;
+"#;
+
+    let module = transform_workflow_hygienic(PluginConfig::default(), input);
+    let code = print_module(&module);
+
+    assert!(
+        code.contains("const signupWorkflow = withDurableExecution("),
+        "{code}"
+    );
+    assert!(code.contains("export default signupWorkflow;"), "{code}");
+    assert!(!code.contains("signupWorkflow1"), "{code}");
+    assert_eq!(
+        manifest(&module)["workflows"]["signupWorkflow"]["handler"],
+        "default"
+    );
+}
+
+#[test]
+fn test_default_export_referenced_elsewhere_in_module() {
+    let input = r#"
+export default async function signup(input) {
+    "use workflow";
+    return input;
+}
+
+export const alias = signup;
+
+export function register(registry) {
+    registry.add(signup);
+}
+"#;
+
+    let module = transform_workflow_hygienic(PluginConfig::default(), input);
+    let code = print_module(&module);
+
+    assert!(
+        code.contains("const signup = withDurableExecution("),
+        "{code}"
+    );
+    assert!(code.contains("export default signup;"), "{code}");
+    assert!(code.contains("export const alias = signup;"), "{code}");
+    assert!(code.contains("registry.add(signup);"), "{code}");
+    assert!(!code.contains("signup1"), "{code}");
+}
+
+#[test]
+fn test_named_export_specifiers() {
+    let input = r#"
+async function signupWorkflow(input) {
+    "use workflow";
+    return input;
+}
+
+const orderWorkflow = async (order) => {
+    "use workflow";
+    return order;
+};
+
+async function internalWorkflow(input) {
+    "use workflow";
+    return input;
+}
+
+export { signupWorkflow as handler, orderWorkflow };
+"#;
+
+    let module = transform_workflow(input);
+    let code = print_module(&module);
+
+    assert!(
+        code.contains("const signupWorkflow = withDurableExecution("),
+        "{code}"
+    );
+    assert!(
+        code.contains("export { signupWorkflow as handler, orderWorkflow };"),
+        "Export specifiers should be kept:\n{code}"
+    );
+
+    let manifest = manifest(&module);
+    let workflows = manifest["workflows"].as_object().unwrap();
+    assert_eq!(
+        workflows.keys().collect::<Vec<_>>(),
+        ["handler", "orderWorkflow", "internalWorkflow"]
+    );
+    assert_eq!(workflows["handler"]["handler"], "handler");
+    assert_eq!(workflows["orderWorkflow"]["handler"], "orderWorkflow");
+    assert_eq!(workflows["internalWorkflow"]["handler"], json!(null));
+}
+
+#[test]
+fn test_workflows_referenced_elsewhere_keep_their_binding() {
+    let input = r#"
+async function signupWorkflow(input) {
+    "use workflow";
+    return input;
+}
+
+const orderWorkflow = async (order) => {
+    "use workflow";
+    return order;
+};
+
+export async function refundWorkflow(input) {
+    "use workflow";
+    return input;
+}
+
+export const auditWorkflow = async (input) => {
+    "use workflow";
+    return input;
+};
+
+const handlers = [signupWorkflow, orderWorkflow, refundWorkflow, auditWorkflow];
+
+export { signupWorkflow as handler, orderWorkflow, handlers };
+"#;
+
+    let module = transform_workflow_hygienic(PluginConfig::default(), input);
+    let code = print_module(&module);
+
+    assert!(
+        code.contains("const signupWorkflow = withDurableExecution("),
+        "{code}"
+    );
+    assert!(
+        code.contains("const orderWorkflow = withDurableExecution("),
+        "{code}"
+    );
+    assert!(
+        code.contains("export const refundWorkflow = withDurableExecution("),
+        "{code}"
+    );
+    assert!(
+        code.contains("export const auditWorkflow = withDurableExecution("),
+        "{code}"
+    );
+    assert!(
+        code.contains("export { signupWorkflow as handler, orderWorkflow, handlers };"),
+        "{code}"
+    );
+    // Hygiene found no second binding to rename
+    assert!(!code.contains("Workflow1"), "{code}");
+}
+
+#[test]
+fn test_shadowed_builtins_are_not_rewritten() {
+    let input = r#"
+async function sleep(ms) {
+    return new Promise((resolve) => setTimeout(resolve, ms));
+}
+
+export async function shadowingWorkflow(input, invoke) {
+    "use workflow";
+    await sleep(100);
+    const waitForCallback = (name) => name;
+    waitForCallback("approval");
+    return invoke("other", input);
+}
+"#;
+
+    let output = print_module(&transform_workflow(input));
+
+    assert!(output.contains("await sleep(100)"), "{output}");
+    assert!(output.contains("waitForCallback(\"approval\")"), "{output}");
+    assert!(
+        output.contains("return invoke(\"other\", input)"),
+        "{output}"
+    );
+    assert!(!output.contains("ctx.wait"), "{output}");
+    assert!(!output.contains("@aws-sdk/client-lambda"), "{output}");
+}
+
+#[test]
+fn test_aliased_builtin_imports() {
+    let input = r#"
+import { sleep as pause, invoke as call } from "@cgalceran/aws-durable-directives";
+import { sleep } from "./utils";
+
+export async function aliasedWorkflow(input) {
+    "use workflow";
+    await pause({ seconds: 5 });
+    await sleep(100);
+    return call("other", input);
+}
+"#;
+
+    let module = transform_workflow(input);
+    let output = print_module(&module);
+
+    assert!(output.contains("ctx.wait({"), "{output}");
+    assert!(output.contains("await sleep(100)"), "{output}");
+    assert!(output.contains("ctx.step(\"invoke:other:1\""), "{output}");
+    assert!(has_import(&module, "@aws-sdk/client-lambda"));
+}
+
+#[test]
+fn test_unused_directive_imports_are_stripped() {
+    let input = r#"
+import { invoke, sleep } from "@cgalceran/aws-durable-directives";
+
+export async function stubbedWorkflow(input) {
+    "use workflow";
+    await sleep({ seconds: 5 });
+    return invoke("other", input);
+}
+"#;
+
+    let module = transform_workflow(input);
+
+    assert!(!has_import(&module, "@cgalceran/aws-durable-directives"));
+}
+
+#[test]
+fn test_used_directive_imports_are_kept() {
+    let input = r#"
+import { sleep, waitForCallback } from "@cgalceran/aws-durable-directives";
+
+export const builtins = { waitForCallback };
+
+export async function stubbedWorkflow(input) {
+    "use workflow";
+    await sleep({ seconds: 5 });
+    return input;
+}
+"#;
+
+    let output = print_module(&transform_workflow(input));
+
+    assert!(
+        output.contains("import { waitForCallback } from \"@cgalceran/aws-durable-directives\""),
+        "{output}"
+    );
+}
+
+#[test]
+fn test_namespace_qualified_builtins() {
+    let input = r#"
+import * as durable from "@cgalceran/aws-durable-directives";
+
+export async function namespacedWorkflow(input) {
+    "use workflow";
+    await durable.sleep({ seconds: 5 });
+    const approval = await durable["waitForCallback"]("approval");
+    return durable.invoke("other", { input, approval });
+}
+"#;
+
+    let module = transform_workflow(input);
+    let output = print_module(&module);
+
+    assert!(output.contains("ctx.wait({"), "{output}");
+    assert!(
+        output.contains("ctx.waitForCallback(\"approval\")"),
+        "{output}"
+    );
+    assert!(output.contains("ctx.step(\"invoke:other:1\""), "{output}");
+    assert!(!output.contains("durable."), "{output}");
+    assert!(has_import(&module, "@aws-sdk/client-lambda"));
+    assert!(!has_import(&module, "@cgalceran/aws-durable-directives"));
+}
+
+#[test]
+fn test_imported_steps_become_step_calls() {
+    let input = r#"
+import { chargeCard as charge } from "../steps/billing";
+import * as billing from "../steps/billing";
+import notify from "./notify" with { type: "step" };
+import { format } from "../utils/format";
+
+export async function orderWorkflow(order) {
+    "use workflow";
+    const charged = await charge(order.total, order.card);
+    await billing.refundCharge(charged.id);
+    await notify(...order.emails);
+    return format(charged);
+}
+"#;
+
+    let config = PluginConfig {
+        filename: Some("src/workflows/order.ts".to_string()),
+        ..Default::default()
+    };
+    let module = transform_workflow_with(config, input);
+    let output = print_module(&module);
+
+    assert!(
+        output.contains(
+            "ctx.step(\"chargeCard:1\", ((arg0, arg1)=>()=>charge(arg0, arg1))(order.total, order.card), {\n        retry: charge.retry,\n        timeout: charge.timeout\n    })"
+        ),
+        "{output}"
+    );
+    assert!(
+        output.contains(
+            "ctx.step(\"refundCharge:1\", ((arg0)=>()=>billing.refundCharge(arg0))(charged.id), {\n        retry: billing.refundCharge.retry,\n        timeout: billing.refundCharge.timeout\n    })"
+        ),
+        "{output}"
+    );
+    assert!(
+        output.contains("ctx.step(\"notify:1\", ((args)=>()=>notify(...args))(["),
+        "{output}"
+    );
+    assert!(output.contains("return format(charged)"), "{output}");
+    assert!(has_import(&module, "../steps/billing"));
+
+    let manifest = manifest(&module);
+    let steps: Vec<&str> = manifest["workflows"]["orderWorkflow"]["steps"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|step| step["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(steps, ["chargeCard:1", "refundCharge:1", "notify:1"]);
+}
+
+#[test]
+fn test_step_module_exports_plain_functions() {
+    let input = r#"
+export async function chargeCard(amount, card) {
+    "use step";
+    return { amount, card };
+}
+
+async function refund(id) {
+    "use step";
+    return id;
+}
+
+export { refund as refundCharge };
+"#;
+
+    let config = PluginConfig {
+        filename: Some("src/steps/billing.ts".to_string()),
+        ..Default::default()
+    };
+    let module = transform_workflow_with(config, input);
+    let output = print_module(&module);
+
+    assert!(!output.contains("use step"), "{output}");
+    assert!(
+        output.contains("export async function chargeCard"),
+        "{output}"
+    );
+    assert!(output.contains("async function refund(id)"), "{output}");
+    assert!(!has_import(&module, "@cgalceran/aws-durable"));
+    assert!(
+        output.contains(
+            "refundCharge: {\n        name: \"refundCharge\",\n        handler: \"refundCharge\",\n        file: \"src/steps/billing.ts\""
+        ),
+        "{output}"
+    );
+    assert!(output.contains("export const __stepMeta"), "{output}");
+}
+
+#[test]
+fn test_closure_step_strategy_keeps_declarations() {
+    let input = r#"
+async function validateEmail(email) {
+    "use step";
+    return email.toLowerCase();
+}
+
+export async function signupWorkflow(input) {
+    "use workflow";
+    const email = await validateEmail(input.email);
+    return validateEmail(email);
+}
+"#;
+
+    let config = PluginConfig {
+        step_strategy: StepStrategy::Closure,
+        ..Default::default()
+    };
+    let output = print_module(&transform_workflow_with(config, input));
+
+    assert!(
+        output.contains("async function validateEmail(email) {\n    return email.toLowerCase();"),
+        "{output}"
+    );
+    assert!(
+        output.contains(
+            "ctx.step(\"validateEmail:1\", ((arg0)=>()=>validateEmail(arg0))(input.email))"
+        ),
+        "{output}"
+    );
+    assert!(
+        output.contains("ctx.step(\"validateEmail:2\", ((arg0)=>()=>validateEmail(arg0))(email))"),
+        "{output}"
+    );
+}
+
+#[test]
+fn test_generated_code_keeps_source_spans() {
+    let input = r#"
+async function validateEmail(email) {
+    "use step";
+    return email.toLowerCase();
+}
+
+export async function signupWorkflow(input) {
+    "use workflow";
+    const email = await validateEmail(input.email);
+    await sleep({ seconds: 5 });
+    return waitForCallback("approval");
+}
+"#;
+
+    /// Source line of every generated call, keyed by its printed callee.
+    struct CallLines<'a> {
+        cm: &'a SourceMap,
+        lines: Vec<(String, usize)>,
+        arrows: Vec<usize>,
+    }
+    impl CallLines<'_> {
+        fn line(&self, span: Span) -> usize {
+            assert!(!span.is_dummy(), "generated node has no span");
+            self.cm.lookup_char_pos(span.lo).line
+        }
+    }
+    impl Visit for CallLines<'_> {
+        fn visit_call_expr(&mut self, call: &CallExpr) {
+            let callee = match &call.callee {
+                Callee::Expr(expr) => match expr.as_ref() {
+                    Expr::Ident(ident) => ident.sym.to_string(),
+                    Expr::Member(MemberExpr {
+                        prop: MemberProp::Ident(prop),
+                        ..
+                    }) => format!("ctx.{}", prop.sym),
+                    _ => String::new(),
+                },
+                _ => String::new(),
+            };
+            if [
+                "withDurableExecution",
+                "ctx.step",
+                "ctx.wait",
+                "ctx.waitForCallback",
+            ]
+            .contains(&callee.as_str())
+            {
+                self.lines.push((callee, self.line(call.span)));
+            }
+            call.visit_children_with(self);
+        }
+
+        fn visit_arrow_expr(&mut self, arrow: &ArrowExpr) {
+            self.arrows.push(self.line(arrow.span));
+            arrow.visit_children_with(self);
+        }
+    }
+
+    let (cm, module) = transform_workflow_mapped(PluginConfig::default(), input);
+    let mut visitor = CallLines {
+        cm: &cm,
+        lines: Vec::new(),
+        arrows: Vec::new(),
+    };
+    module.visit_with(&mut visitor);
+
+    let lines: Vec<(&str, usize)> = visitor
+        .lines
+        .iter()
+        .map(|(callee, line)| (callee.as_str(), *line))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            ("withDurableExecution", 7),
+            ("ctx.step", 9),
+            ("ctx.wait", 10),
+            ("ctx.waitForCallback", 11),
+        ]
+    );
+    // The workflow handler, then the step's binder and closure
+    assert_eq!(visitor.arrows, [7, 2, 2]);
+}
+
+#[test]
+fn test_promise_combinators_become_parallel() {
+    let input = r#"
+async function chargeCard(order) {
+    "use step";
+    return order.total;
+}
+
+async function reserveStock(order) {
+    "use step";
+    return order.items;
+}
+
+export async function orderWorkflow(order) {
+    "use workflow";
+    const [charge, stock] = await Promise.all([chargeCard(order), reserveStock(order)]);
+    await Promise.race([invoke("fastQuote", order), invoke("slowQuote", order)]);
+    await Promise.all([fetchRates(), Promise.resolve(1)]);
+    return { charge, stock };
+}
+"#;
+
+    let module = transform_workflow(input);
+    let output = print_module(&module);
+
+    assert!(
+        output.contains(
+            "await ctx.parallel(\"parallel:1\", [\n        (ctx)=>ctx.step(\"chargeCard:1\""
+        ),
+        "{output}"
+    );
+    assert!(
+        output.contains("(ctx)=>ctx.step(\"reserveStock:1\""),
+        "{output}"
+    );
+    assert!(output.contains("mode: \"all\""), "{output}");
+    assert!(output.contains("ctx.parallel(\"parallel:2\""), "{output}");
+    assert!(output.contains("mode: \"race\""), "{output}");
+    // Without durable calls the combinator is left alone
+    assert!(
+        output.contains("await Promise.all([\n        fetchRates(),"),
+        "{output}"
+    );
+
+    assert_eq!(
+        manifest(&module)["workflows"]["orderWorkflow"]["parallels"],
+        json!([
+            {
+                "id": "parallel:1",
+                "mode": "all",
+                "branches": [["chargeCard:1"], ["reserveStock:1"]],
+                "location": { "line": 14, "column": 35 }
+            },
+            {
+                "id": "parallel:2",
+                "mode": "race",
+                "branches": [["invoke:fastQuote:1"], ["invoke:slowQuote:1"]],
+                "location": { "line": 15, "column": 11 }
+            }
+        ])
+    );
+}
+
+#[test]
+fn test_shadowed_promise_is_not_parallel() {
+    let input = r#"
+async function chargeCard(order) {
+    "use step";
+    return order.total;
+}
+
+export async function orderWorkflow(order, ctx, Promise) {
+    "use workflow";
+    return Promise.all([chargeCard(order)]);
+}
+"#;
+
+    let output = print_module(&transform_workflow(input));
+
+    assert!(output.contains("Promise.all(["), "{output}");
+    assert!(!output.contains("ctx.parallel"), "{output}");
+}
+
+#[test]
+fn test_map_over_step() {
+    let input = r#"
+import { map } from "@cgalceran/aws-durable-directives";
+import { sendEmail } from "../steps/email";
+
+async function processItem(item, index) {
+    "use step";
+    return item.id + index;
+}
+
+export async function batchWorkflow(items) {
+    "use workflow";
+    const results = await map(items, processItem, { concurrency: 5 });
+    await map(results, sendEmail);
+    return results;
+}
+"#;
+
+    let config = PluginConfig {
+        filename: Some("src/workflows/batch.ts".to_string()),
+        ..Default::default()
+    };
+    let module = transform_workflow_with(config, input);
+    let output = print_module(&module);
+
+    assert!(
+        output.contains(
+            "await ctx.map(\"map:processItem:1\", items, async (item, index)=>{\n        return item.id + index;\n    }, {\n        concurrency: 5\n    })"
+        ),
+        "{output}"
+    );
+    assert!(
+        output.contains(
+            "await ctx.map(\"map:sendEmail:1\", results, sendEmail, {\n        retry: sendEmail.retry,\n        timeout: sendEmail.timeout\n    })"
+        ),
+        "{output}"
+    );
+    assert!(!has_import(&module, "@cgalceran/aws-durable-directives"));
+
+    assert_eq!(
+        manifest(&module)["workflows"]["batchWorkflow"]["maps"],
+        json!([
+            {
+                "id": "map:processItem:1",
+                "step": "processItem",
+                "concurrency": 5,
+                "location": { "line": 12, "column": 27 }
+            },
+            {
+                "id": "map:sendEmail:1",
+                "step": "sendEmail",
+                "concurrency": null,
+                "location": { "line": 13, "column": 11 }
+            }
+        ])
+    );
+}
+
+#[test]
+fn test_step_retry_policy() {
+    let input = r#"
+async function chargeCard(order) {
+    "use step";
+    return await stripe.charge(order);
+}
+chargeCard.retry = {
+    maxAttempts: 5,
+    backoff: "exponential",
+    initialDelay: { seconds: 1 },
+    jitter: true,
+    retryIf: (error) => error.name === "RateLimited",
+};
+
+export async function orderWorkflow(order) {
+    "use workflow";
+    return await chargeCard(order);
+}
+"#;
+
+    let module = transform_workflow(input);
+    let output = print_module(&module);
+
+    assert!(
+        output.contains("})(order), {\n        retry: {\n            maxAttempts: 5,"),
+        "{output}"
+    );
+    assert!(
+        output.contains("retryIf: (error)=>error.name === \"RateLimited\""),
+        "{output}"
+    );
+    assert!(!output.contains("chargeCard.retry"), "{output}");
+
+    assert_eq!(
+        manifest(&module)["workflows"]["orderWorkflow"]["steps"][0]["retry"],
+        json!({
+            "maxAttempts": 5,
+            "backoff": "exponential",
+            "initialDelay": { "seconds": 1 },
+            "jitter": true
+        })
+    );
+}
+
+#[test]
+fn test_exported_step_keeps_retry_policy() {
+    let input = r#"
+export async function chargeCard(order) {
+    "use step";
+    return await stripe.charge(order);
+}
+chargeCard.retry = { maxAttempts: 3 };
+"#;
+
+    let config = PluginConfig {
+        filename: Some("src/steps/billing.ts".to_string()),
+        ..Default::default()
+    };
+    let module = transform_workflow_with(config, input);
+    let output = print_module(&module);
+
+    assert!(
+        output.contains("chargeCard.retry = {\n    maxAttempts: 3\n};"),
+        "{output}"
+    );
+    assert!(
+        output.contains("retry: {\n            maxAttempts: 3\n        }"),
+        "{output}"
+    );
+}
+
+#[test]
+fn test_step_and_workflow_timeouts() {
+    let input = r#"
+async function chargeCard(order) {
+    "use step";
+    return await stripe.charge(order);
+}
+chargeCard.timeout = { seconds: 30 };
+
+export async function orderWorkflow(order) {
+    "use workflow";
+    return await chargeCard(order);
+}
+orderWorkflow.timeout = { hours: 1 };
+"#;
+
+    let module = transform_workflow(input);
+    let output = print_module(&module);
+
+    assert!(
+        output.contains(
+            "})(order), {\n        timeout: {\n            seconds: 30\n        }\n    });"
+        ),
+        "{output}"
+    );
+    assert!(
+        output.contains("}, {\n    timeout: {\n        hours: 1\n    }\n});"),
+        "{output}"
+    );
+    assert!(!output.contains(".timeout ="), "{output}");
+
+    let manifest = manifest(&module);
+    let workflow = &manifest["workflows"]["orderWorkflow"];
+    assert_eq!(workflow["timeout"], json!({ "hours": 1 }));
+    assert_eq!(workflow["steps"][0]["timeout"], json!({ "seconds": 30 }));
+}
+
+#[test]
+fn test_compensated_steps() {
+    let input = r#"
+import { compensate } from "@cgalceran/aws-durable-directives";
+import { refundCard } from "../steps/billing";
+
+async function chargeCard(order) {
+    "use step";
+    return await stripe.charge(order);
+}
+
+async function releaseStock(reservation) {
+    "use step";
+    await inventory.release(reservation.id);
+}
+
+export async function orderWorkflow(order) {
+    "use workflow";
+    const charge = await compensate(chargeCard(order), refundCard);
+    const reservation = await compensate(invoke("reserve-stock", order), releaseStock);
+    return { charge, reservation };
+}
+"#;
+
+    let config = PluginConfig {
+        filename: Some("src/workflows/order.ts".to_string()),
+        ..Default::default()
+    };
+    let module = transform_workflow_with(config, input);
+    let output = print_module(&module);
+
+    assert!(
+        output.contains(
+            "await ctx.compensate(\"compensate:refundCard:1\", ctx.step(\"chargeCard:1\""
+        ),
+        "{output}"
+    );
+    assert!(
+        output.contains("})(order)), refundCard, {\n        retry: refundCard.retry,"),
+        "{output}"
+    );
+    assert!(
+        output.contains(
+            "}), async (reservation)=>{\n        await inventory.release(reservation.id);\n    })"
+        ),
+        "{output}"
+    );
+    assert!(!has_import(&module, "@cgalceran/aws-durable-directives"));
+
+    assert_eq!(
+        manifest(&module)["workflows"]["orderWorkflow"]["compensations"],
+        json!([
+            {
+                "id": "compensate:refundCard:1",
+                "step": "refundCard",
+                "compensates": "chargeCard:1",
+                "location": { "line": 17, "column": 26 }
+            },
+            {
+                "id": "compensate:releaseStock:1",
+                "step": "releaseStock",
+                "compensates": "invoke:reserve-stock:1",
+                "location": { "line": 18, "column": 31 }
+            }
+        ])
+    );
+}
+
+#[test]
+fn test_compensated_steps_in_parallel_branches() {
+    let input = r#"
+import { compensate } from "@cgalceran/aws-durable-directives";
+
+async function chargeCard(order) {
+    "use step";
+    return order.total;
+}
+
+async function refundCard(charge) {
+    "use step";
+}
+
+async function reserveStock(order) {
+    "use step";
+    return order.items;
+}
+
+async function releaseStock(reservation) {
+    "use step";
+}
+
+export async function orderWorkflow(order) {
+    "use workflow";
+    await Promise.all([
+        compensate(chargeCard(order), refundCard),
+        compensate(reserveStock(order), releaseStock),
+    ]);
+}
+"#;
+
+    let module = transform_workflow(input);
+    let output = print_module(&module);
+
+    // Each branch registers its undo on the branch context, so the runtime
+    // rolls it back when a sibling branch fails
+    assert!(
+        output.contains(
+            "(ctx)=>ctx.compensate(\"compensate:refundCard:1\", ctx.step(\"chargeCard:1\""
+        ),
+        "{output}"
+    );
+    assert!(
+        output.contains(
+            "(ctx)=>ctx.compensate(\"compensate:releaseStock:1\", ctx.step(\"reserveStock:1\""
+        ),
+        "{output}"
+    );
+    assert!(output.contains("mode: \"all\""), "{output}");
+}
@@ -1,10 +1,27 @@
+use std::collections::HashMap;
+use swc_core::common::errors::SourceMapperDyn;
+use swc_core::common::sync::Lrc;
+use swc_core::common::Span;
 use swc_core::ecma::ast::*;
-use swc_core::ecma::visit::VisitMut;
+use swc_core::ecma::visit::{Visit, VisitMut, VisitMutWith, VisitWith};
 
 use crate::codegen;
-use crate::collector::CollectedInfo;
-use crate::config::{PluginConfig, TransformMode};
+use crate::collector::{
+    option_assignment, workflow_import_specifiers, workflow_name_from_path, CollectedInfo,
+    StepFnInfo, WorkflowFnInfo, WorkflowImportInfo,
+};
+use crate::config::{PluginConfig, StepStrategy, TransformMode};
+use crate::diagnostics::{self, DiagnosticCode};
 use crate::directive::{is_use_step_directive, is_use_workflow_directive};
+use crate::imports::{
+    is_workflow_import, manifest_module_exports, manifest_workflow_name,
+    strip_unused_directive_imports,
+};
+use crate::meta::{
+    manifest_json, object_prop, static_props, static_value, strip_type_wrappers, CallbackMeta,
+    CompensationMeta, InvokeMeta, Location, MapMeta, ParallelMeta, SleepMeta, StepExportMeta,
+    WorkflowMeta,
+};
 
 /// Pass 2: Mutable visitor that transforms the AST.
 pub struct WorkflowTransformer {
@@ -12,6 +29,16 @@ pub struct WorkflowTransformer {
     pub info: CollectedInfo,
     /// Whether we're currently inside a workflow function body.
     inside_workflow: bool,
+    /// The durable context binding of the workflow being transformed.
+    ctx: Ident,
+    /// Call-site ordinals handed out per step base name in the current workflow.
+    step_ordinals: HashMap<String, usize>,
+    /// Metadata of the workflow being transformed.
+    current_meta: WorkflowMeta,
+    /// Metadata of every workflow transformed so far, in source order.
+    metas: Vec<WorkflowMeta>,
+    /// Resolves call-site spans to lines and columns for the manifest.
+    source_map: Option<Lrc<SourceMapperDyn>>,
 }
 
 impl WorkflowTransformer {
@@ -20,9 +47,37 @@ impl WorkflowTransformer {
             config,
             info,
             inside_workflow: false,
+            ctx: codegen::workflow_ctx_ident(&[]),
+            step_ordinals: HashMap::new(),
+            current_meta: WorkflowMeta::default(),
+            metas: Vec::new(),
+            source_map: None,
         }
     }
 
+    /// Record source locations of call sites in the workflow manifest.
+    pub fn with_source_map(mut self, source_map: Lrc<SourceMapperDyn>) -> Self {
+        self.source_map = Some(source_map);
+        self
+    }
+
+    /// The `__workflowManifest` JSON of the transformed module, for hosts that
+    /// read it from the transform output instead of the emitted code.
+    pub fn manifest(&self) -> Option<String> {
+        (!self.metas.is_empty()).then(|| manifest_json(&self.metas).to_string())
+    }
+
+    fn locate(&self, span: Span) -> Option<Location> {
+        if span.is_dummy() {
+            return None;
+        }
+        let loc = self.source_map.as_ref()?.lookup_char_pos(span.lo);
+        Some(Location {
+            line: loc.line,
+            column: loc.col_display + 1,
+        })
+    }
+
     fn is_step_fn_call(&self, callee: &Callee) -> Option<String> {
         if let Callee::Expr(expr) = callee {
             if let Expr::Ident(ident) = expr.as_ref() {
@@ -35,25 +90,51 @@ impl WorkflowTransformer {
         None
     }
 
-    fn is_special_call(&self, callee: &Callee) -> Option<String> {
-        if let Callee::Expr(expr) = callee {
-            if let Expr::Ident(ident) = expr.as_ref() {
-                let name = ident.sym.as_ref();
-                if matches!(name, "invoke" | "sleep" | "waitForCallback") {
-                    return Some(name.to_string());
-                }
-            }
-        }
-        None
+    /// The built-in a call targets, if its callee resolves to one rather than
+    /// to a local binding of the same name.
+    fn is_special_call(&self, callee: &Callee) -> Option<&'static str> {
+        self.info.builtin_callee(callee)
+    }
+
+    /// Allocate the deterministic id for the next call site of `base`:
+    /// `validateEmail:1`, `validateEmail:2`, … in source order within the
+    /// workflow. Repeats of one call site (loops) are told apart at runtime by
+    /// an occurrence suffix, see `ctx.step` in the runtime.
+    fn next_step_id(&mut self, base: &str) -> String {
+        let ordinal = self.step_ordinals.entry(base.to_string()).or_insert(0);
+        *ordinal += 1;
+        format!("{}:{}", base, ordinal)
     }
 
-    fn is_step_fn_name(&self, name: &str) -> bool {
-        self.info.step_fn_names.iter().any(|n| n == name)
+    /// Steps whose declarations are dropped because every call inlines them.
+    /// Exported steps are kept for workflows in other modules, and every step
+    /// is kept under the closure strategy.
+    fn is_inlined_step_fn(&self, name: &str) -> bool {
+        self.config.step_strategy == StepStrategy::Inline
+            && self
+                .info
+                .step_fns
+                .get(name)
+                .is_some_and(|step| !step.is_exported)
     }
 
-    fn find_workflow_fn(&self, name: &str) -> Option<&crate::collector::WorkflowFnInfo> {
+    /// `step.retry = ...` of an inlined step or `workflow.timeout = ...`: the
+    /// option is passed to the generated call, and its target is gone.
+    fn is_moved_option_assignment(&self, stmt: &Stmt) -> bool {
+        option_assignment(stmt).is_some_and(|(target, option, _)| {
+            self.is_inlined_step_fn(&target.sym)
+                || (option == "timeout" && self.find_workflow_fn(&target.sym).is_some())
+        })
+    }
+
+    fn find_workflow_fn(&self, name: &str) -> Option<&WorkflowFnInfo> {
         self.info.workflow_fns.iter().find(|w| w.name == name)
     }
+
+    /// Default exports may be anonymous, so they are matched by span.
+    fn find_workflow_fn_by_span(&self, span: Span) -> Option<&WorkflowFnInfo> {
+        self.info.workflow_fns.iter().find(|w| w.span == span)
+    }
 }
 
 impl VisitMut for WorkflowTransformer {
@@ -63,23 +144,50 @@ impl VisitMut for WorkflowTransformer {
             TransformMode::Client => self.transform_client_module(module),
         }
     }
+
+    /// Reaches every expression inside a workflow body, whatever statement or
+    /// expression it is nested in. Children are rewritten first so step and
+    /// built-in calls used as arguments are transformed before their parent.
+    fn visit_mut_expr(&mut self, expr: &mut Expr) {
+        if self.inside_workflow {
+            if let Expr::Call(call) = expr {
+                if let Some(rewritten) = self.rewrite_parallel(call) {
+                    *expr = rewritten;
+                    return;
+                }
+            }
+        }
+        expr.visit_mut_children_with(self);
+        if !self.inside_workflow {
+            return;
+        }
+        if let Expr::Call(call) = expr {
+            if let Some(rewritten) = self.rewrite_call(call) {
+                *expr = rewritten;
+            }
+        }
+    }
 }
 
 impl WorkflowTransformer {
     // ── Workflow Mode ───────────────────────────────────────────
 
     fn transform_workflow_module(&mut self, module: &mut Module) {
-        // No workflow directives found → skip entirely
-        if self.info.workflow_fns.is_empty() && !self.info.has_module_workflow_directive {
+        let has_workflows =
+            !self.info.workflow_fns.is_empty() || self.info.has_module_workflow_directive;
+        // No workflow directives or exported steps found → skip entirely
+        if !has_workflows && !self.info.has_exported_steps() {
             return;
         }
 
         let mut new_items: Vec<ModuleItem> = Vec::new();
 
         // 1. Prepend SDK imports
-        new_items.push(codegen::create_sdk_import(&self.config.package_name));
-        if self.info.has_invoke {
-            new_items.push(codegen::create_lambda_sdk_import());
+        if has_workflows {
+            new_items.push(codegen::create_sdk_import(&self.config.package_name));
+            if self.info.has_invoke {
+                new_items.push(codegen::create_lambda_sdk_import());
+            }
         }
 
         // 2. Process each module item
@@ -88,24 +196,29 @@ impl WorkflowTransformer {
                 // Remove top-level "use workflow" directives
                 ModuleItem::Stmt(stmt) if is_use_workflow_directive(stmt) => continue,
 
+                // Remove options that move into the generated calls
+                ModuleItem::Stmt(stmt) if self.is_moved_option_assignment(stmt) => continue,
+
                 // Handle function declarations (both step and workflow)
                 ModuleItem::Stmt(Stmt::Decl(Decl::Fn(fn_decl))) => {
                     let name = fn_decl.ident.sym.to_string();
 
                     // Remove step function declarations (they get inlined)
-                    if self.is_step_fn_name(&name) {
+                    if self.is_inlined_step_fn(&name) {
                         continue;
                     }
 
                     // Transform workflow function declarations
                     if let Some(wf_info) = self.find_workflow_fn(&name).cloned() {
                         if let Some(body) = &fn_decl.function.body {
-                            let stmts = self.transform_workflow_body(&body.stmts);
+                            let stmts = self.transform_workflow_body(&wf_info, &body.stmts);
                             new_items.push(codegen::create_with_durable_execution_call(
-                                &wf_info.name,
+                                fn_decl.ident.clone(),
+                                &wf_info.params,
                                 stmts,
+                                workflow_options(&wf_info),
                                 false,
-                                false,
+                                wf_info.span,
                             ));
                         }
                         continue;
@@ -120,7 +233,7 @@ impl WorkflowTransformer {
                     // Check if this is a step fn var decl to remove
                     let is_step = var_decl.decls.iter().any(|d| {
                         if let Pat::Ident(ident) = &d.name {
-                            self.is_step_fn_name(&ident.sym.to_string())
+                            self.is_inlined_step_fn(ident.sym.as_ref())
                         } else {
                             false
                         }
@@ -137,13 +250,16 @@ impl WorkflowTransformer {
                             if let Some(wf_info) = self.find_workflow_fn(&name).cloned() {
                                 if let Some(init) = &d.init {
                                     if let Some(body) = extract_arrow_body(init) {
-                                        let stmts = self.transform_workflow_body(&body.stmts);
+                                        let stmts =
+                                            self.transform_workflow_body(&wf_info, &body.stmts);
                                         new_items.push(
                                             codegen::create_with_durable_execution_call(
-                                                &wf_info.name,
+                                                ident.id.clone(),
+                                                &wf_info.params,
                                                 stmts,
+                                                workflow_options(&wf_info),
                                                 false,
-                                                false,
+                                                wf_info.span,
                                             ),
                                         );
                                         handled = true;
@@ -165,12 +281,14 @@ impl WorkflowTransformer {
                     let name = fn_decl.ident.sym.to_string();
                     if let Some(wf_info) = self.find_workflow_fn(&name).cloned() {
                         if let Some(body) = &fn_decl.function.body {
-                            let stmts = self.transform_workflow_body(&body.stmts);
+                            let stmts = self.transform_workflow_body(&wf_info, &body.stmts);
                             new_items.push(codegen::create_with_durable_execution_call(
-                                &wf_info.name,
+                                fn_decl.ident.clone(),
+                                &wf_info.params,
                                 stmts,
+                                workflow_options(&wf_info),
                                 true,
-                                wf_info.is_default_export,
+                                wf_info.span,
                             ));
                         }
                     } else {
@@ -190,13 +308,16 @@ impl WorkflowTransformer {
                             if let Some(wf_info) = self.find_workflow_fn(&name).cloned() {
                                 if let Some(init) = &d.init {
                                     if let Some(body) = extract_arrow_body(init) {
-                                        let stmts = self.transform_workflow_body(&body.stmts);
+                                        let stmts =
+                                            self.transform_workflow_body(&wf_info, &body.stmts);
                                         new_items.push(
                                             codegen::create_with_durable_execution_call(
-                                                &wf_info.name,
+                                                ident.id.clone(),
+                                                &wf_info.params,
                                                 stmts,
+                                                workflow_options(&wf_info),
                                                 true,
-                                                wf_info.is_default_export,
+                                                wf_info.span,
                                             ),
                                         );
                                         handled = true;
@@ -210,6 +331,51 @@ impl WorkflowTransformer {
                     }
                 }
 
+                // Transform default-exported workflow functions, keeping them
+                // the module's default export
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultDecl(ExportDefaultDecl {
+                    decl: DefaultDecl::Fn(fn_expr),
+                    ..
+                })) => {
+                    match self
+                        .find_workflow_fn_by_span(fn_expr.function.span)
+                        .cloned()
+                    {
+                        Some(wf_info) => {
+                            let body = fn_expr.function.body.as_ref().map_or(&[][..], |b| &b.stmts);
+                            let stmts = self.transform_workflow_body(&wf_info, body);
+                            new_items.extend(codegen::create_default_with_durable_execution(
+                                fn_expr.ident.clone(),
+                                &wf_info.params,
+                                stmts,
+                                workflow_options(&wf_info),
+                                wf_info.span,
+                            ));
+                        }
+                        None => new_items.push(item),
+                    }
+                }
+
+                // Transform default-exported workflow expressions (arrow fns)
+                ModuleItem::ModuleDecl(ModuleDecl::ExportDefaultExpr(export_default)) => {
+                    let wf_info = extract_fn_span(&export_default.expr)
+                        .and_then(|span| self.find_workflow_fn_by_span(span))
+                        .cloned();
+                    match (wf_info, extract_arrow_body(&export_default.expr)) {
+                        (Some(wf_info), Some(body)) => {
+                            let stmts = self.transform_workflow_body(&wf_info, &body.stmts);
+                            new_items.extend(codegen::create_default_with_durable_execution(
+                                None,
+                                &wf_info.params,
+                                stmts,
+                                workflow_options(&wf_info),
+                                wf_info.span,
+                            ));
+                        }
+                        _ => new_items.push(item),
+                    }
+                }
+
                 // Keep everything else
                 _ => {
                     new_items.push(item);
@@ -217,140 +383,509 @@ impl WorkflowTransformer {
             }
         }
 
-        // 3. Append __workflowMeta export
-        if let Some(wf) = self.info.workflow_fns.first() {
-            let step_names: Vec<String> = self.info.step_fns.keys().cloned().collect();
-            new_items.push(codegen::create_workflow_meta_export(&wf.name, &step_names));
+        self.report_stray_step_references(&new_items);
+        strip_unused_directive_imports(&mut new_items);
+        new_items.visit_mut_with(&mut StepDirectiveStripper);
+
+        // 3. Append __workflowMeta and __stepMeta exports
+        if !self.metas.is_empty() {
+            new_items.extend(codegen::create_workflow_meta_exports(&self.metas));
+        }
+        if self.info.has_exported_steps() {
+            new_items.push(codegen::create_step_meta_export(&self.step_export_metas()));
         }
 
         module.body = new_items;
     }
 
-    fn transform_workflow_body(&mut self, stmts: &[Stmt]) -> Vec<Stmt> {
+    fn transform_workflow_body(&mut self, wf_info: &WorkflowFnInfo, stmts: &[Stmt]) -> Vec<Stmt> {
         self.inside_workflow = true;
-        let result: Vec<Stmt> = stmts
+        self.ctx = codegen::workflow_ctx_ident(&wf_info.params);
+        self.step_ordinals.clear();
+        self.current_meta = WorkflowMeta::new(&wf_info.public_name, codegen::STEP_ID_SCHEME);
+        self.current_meta.handler = match (wf_info.is_exported, wf_info.is_default_export) {
+            (_, true) => Some("default".to_string()),
+            (true, false) => Some(wf_info.public_name.clone()),
+            (false, false) => None,
+        };
+        self.current_meta.file = self.config.filename.clone();
+        self.current_meta.record_params(&wf_info.params);
+        self.current_meta.timeout = wf_info.timeout.as_ref().and_then(literal_duration);
+        let mut result: Vec<Stmt> = stmts
             .iter()
             .filter(|s| !is_use_workflow_directive(s) && !is_use_step_directive(s))
-            .map(|s| self.transform_stmt(s))
+            .cloned()
             .collect();
+        result.visit_mut_with(self);
         self.inside_workflow = false;
+        self.metas.push(std::mem::take(&mut self.current_meta));
         result
     }
 
-    fn transform_stmt(&mut self, stmt: &Stmt) -> Stmt {
-        match stmt {
-            Stmt::Expr(expr_stmt) => {
-                let new_expr = self.transform_expr(&expr_stmt.expr);
-                Stmt::Expr(ExprStmt {
-                    span: expr_stmt.span,
-                    expr: Box::new(new_expr),
-                })
-            }
-            Stmt::Decl(Decl::Var(var_decl)) => {
-                let new_decls: Vec<VarDeclarator> = var_decl
-                    .decls
-                    .iter()
-                    .map(|d| VarDeclarator {
-                        span: d.span,
-                        name: d.name.clone(),
-                        init: d.init.as_ref().map(|e| Box::new(self.transform_expr(e))),
-                        definite: d.definite,
-                    })
-                    .collect();
-                Stmt::Decl(Decl::Var(Box::new(VarDecl {
-                    span: var_decl.span,
-                    kind: var_decl.kind,
-                    declare: var_decl.declare,
-                    decls: new_decls,
-                    ctxt: var_decl.ctxt,
-                })))
+    /// Any step reference left after rewriting sits outside a workflow body,
+    /// where no durable context exists and the step declaration is gone.
+    fn report_stray_step_references(&self, items: &[ModuleItem]) {
+        if self.config.step_strategy == StepStrategy::Closure {
+            return;
+        }
+
+        struct StepReferenceFinder<'a> {
+            info: &'a CollectedInfo,
+        }
+        impl Visit for StepReferenceFinder<'_> {
+            fn visit_expr(&mut self, expr: &Expr) {
+                if let Expr::Ident(ident) = expr {
+                    let step = self.info.step_fns.get(ident.sym.as_ref());
+                    if step.is_some_and(|step| !step.is_exported) {
+                        diagnostics::error(
+                            ident.span,
+                            DiagnosticCode::StepOutsideWorkflow,
+                            &format!(
+                                "step function `{}` can only be used inside a \"use workflow\" function",
+                                ident.sym
+                            ),
+                        );
+                    }
+                }
+                expr.visit_children_with(self);
             }
-            Stmt::Return(ret) => Stmt::Return(ReturnStmt {
-                span: ret.span,
-                arg: ret
-                    .arg
-                    .as_ref()
-                    .map(|e| Box::new(self.transform_expr(e))),
-            }),
-            // For other statement types, clone as-is
-            other => other.clone(),
         }
+
+        items.visit_with(&mut StepReferenceFinder { info: &self.info });
     }
 
-    fn transform_expr(&mut self, expr: &Expr) -> Expr {
-        match expr {
-            Expr::Call(call) => {
-                // Check for step function call
-                if let Some(step_name) = self.is_step_fn_call(&call.callee) {
-                    if let Some(step_info) = self.info.step_fns.get(&step_name).cloned() {
-                        let body_stmts: Vec<Stmt> = step_info
-                            .body
-                            .stmts
-                            .iter()
-                            .filter(|s| !is_use_step_directive(s))
-                            .cloned()
-                            .collect();
-                        return codegen::create_ctx_step_call(&step_name, body_stmts);
-                    }
+    /// Rewrite a step or built-in call into its durable equivalent. The call's
+    /// arguments have already been rewritten by the time this runs.
+    fn rewrite_call(&mut self, call: &CallExpr) -> Option<Expr> {
+        // Check for step function call
+        if let Some(step_name) = self.is_step_fn_call(&call.callee) {
+            let step_info = self.info.step_fns.get(&step_name)?.clone();
+            let step_id = self.next_step_id(&step_name);
+            let location = self.locate(call.span);
+            let retry = step_info.retry.as_ref().and_then(static_props);
+            let timeout = step_info.timeout.as_ref().and_then(literal_duration);
+            self.current_meta
+                .record_step(&step_name, &step_id, retry, timeout, location);
+            let options = merge_options(None, local_step_options(&step_info));
+            let step_call = match (self.config.step_strategy, &call.callee) {
+                (StepStrategy::Closure, Callee::Expr(callee)) => codegen::create_step_fn_call(
+                    &self.ctx,
+                    &step_id,
+                    (**callee).clone(),
+                    call.args.clone(),
+                    call.span,
+                ),
+                _ => {
+                    let body_stmts: Vec<Stmt> = step_info
+                        .body
+                        .stmts
+                        .into_iter()
+                        .filter(|s| !is_use_step_directive(s))
+                        .collect();
+                    codegen::create_bound_ctx_step_call(
+                        &self.ctx,
+                        &step_id,
+                        step_info.params,
+                        call.args.clone(),
+                        body_stmts,
+                        call.span,
+                        step_info.span,
+                    )
                 }
+            };
+            return Some(match options {
+                Some(options) => codegen::with_step_options(step_call, options),
+                None => step_call,
+            });
+        }
 
-                // Check for special calls
-                if let Some(special_name) = self.is_special_call(&call.callee) {
-                    match special_name.as_str() {
-                        "invoke" => {
-                            if call.args.len() >= 2 {
-                                return codegen::create_invoke_step(
-                                    &call.args[0].expr,
-                                    &call.args[1].expr,
-                                );
-                            }
-                        }
-                        "sleep" => {
-                            if let Some(arg) = call.args.first() {
-                                return codegen::create_ctx_wait_call(&arg.expr);
-                            }
-                        }
-                        "waitForCallback" => {
-                            return codegen::create_ctx_wait_for_callback_call(&call.args);
-                        }
-                        _ => {}
+        // Check for a step imported from another module
+        if let Some(step_name) = self.info.imported_step_callee(&call.callee) {
+            let step_id = self.next_step_id(&step_name);
+            let location = self.locate(call.span);
+            self.current_meta
+                .record_step(&step_name, &step_id, None, None, location);
+            let Callee::Expr(callee) = &call.callee else {
+                return None;
+            };
+            let step_call = codegen::create_step_fn_call(
+                &self.ctx,
+                &step_id,
+                (**callee).clone(),
+                call.args.clone(),
+                call.span,
+            );
+            let options = merge_options(None, imported_step_options(callee));
+            return Some(match options {
+                Some(options) => codegen::with_step_options(step_call, options),
+                None => step_call,
+            });
+        }
+
+        // Check for special calls
+        match self.is_special_call(&call.callee)? {
+            "invoke" if call.args.len() < 2 => {
+                diagnostics::error(
+                    call.span,
+                    DiagnosticCode::InvalidBuiltinArguments,
+                    "`invoke()` expects a function name and a payload",
+                );
+                None
+            }
+            "invoke" => {
+                let function_name = match call.args[0].expr.as_ref() {
+                    Expr::Lit(Lit::Str(fn_name)) => {
+                        Some(fn_name.value.to_string_lossy().into_owned())
                     }
+                    _ => None,
+                };
+                let step_id = match &function_name {
+                    Some(fn_name) => self.next_step_id(&format!("invoke:{}", fn_name)),
+                    None => self.next_step_id("invoke"),
+                };
+                self.current_meta.invokes.push(InvokeMeta {
+                    function_name,
+                    id: step_id.clone(),
+                    location: self.locate(call.span),
+                });
+                Some(codegen::create_invoke_step(
+                    &self.ctx,
+                    &step_id,
+                    &call.args[0].expr,
+                    &call.args[1].expr,
+                    call.span,
+                ))
+            }
+            "sleep" => match call.args.first() {
+                Some(arg) => {
+                    self.current_meta.sleeps.push(SleepMeta {
+                        duration: static_value(&arg.expr),
+                        location: self.locate(call.span),
+                    });
+                    Some(codegen::create_ctx_wait_call(
+                        &self.ctx, &arg.expr, call.span,
+                    ))
                 }
-
-                // Recurse into call arguments
-                let new_args: Vec<ExprOrSpread> = call
+                None => {
+                    diagnostics::error(
+                        call.span,
+                        DiagnosticCode::InvalidBuiltinArguments,
+                        "`sleep()` expects a duration",
+                    );
+                    None
+                }
+            },
+            "waitForCallback" => {
+                let name = match call.args.first().map(|a| &*a.expr) {
+                    Some(Expr::Lit(Lit::Str(name))) => {
+                        Some(name.value.to_string_lossy().into_owned())
+                    }
+                    _ => None,
+                };
+                let timeout = call
                     .args
+                    .get(2)
+                    .and_then(|opts| object_prop(&opts.expr, "timeout"))
+                    .and_then(static_value);
+                self.current_meta.callbacks.push(CallbackMeta {
+                    name,
+                    timeout,
+                    location: self.locate(call.span),
+                });
+                Some(codegen::create_ctx_wait_for_callback_call(
+                    &self.ctx, &call.args, call.span,
+                ))
+            }
+            "map" => {
+                let step = match call.args.as_slice() {
+                    [items, step, ..] if items.spread.is_none() && step.spread.is_none() => {
+                        self.step_fn_arg(&step.expr)
+                    }
+                    _ => None,
+                };
+                let Some((step_name, mapper, step_options)) = step else {
+                    diagnostics::error(
+                        call.span,
+                        DiagnosticCode::InvalidBuiltinArguments,
+                        "`map()` expects an array and a step function",
+                    );
+                    return None;
+                };
+                let map_id = self.next_step_id(&format!("map:{}", step_name));
+                let options = call.args.get(2).map(|opts| (*opts.expr).clone());
+                self.current_meta.maps.push(MapMeta {
+                    id: map_id.clone(),
+                    step: step_name,
+                    concurrency: options
+                        .as_ref()
+                        .and_then(|opts| object_prop(opts, "concurrency"))
+                        .and_then(static_value),
+                    location: self.locate(call.span),
+                });
+                let options = merge_options(options, step_options);
+                Some(codegen::create_ctx_map_call(
+                    &self.ctx,
+                    &map_id,
+                    (*call.args[0].expr).clone(),
+                    mapper,
+                    options,
+                    call.span,
+                ))
+            }
+            "compensate" => {
+                let parts = match call.args.as_slice() {
+                    [forward, undo, ..] if forward.spread.is_none() && undo.spread.is_none() => {
+                        self.durable_call_id(&forward.expr)
+                            .zip(self.step_fn_arg(&undo.expr))
+                    }
+                    _ => None,
+                };
+                let Some((compensates, (step_name, undo, step_options))) = parts else {
+                    diagnostics::error(
+                        call.span,
+                        DiagnosticCode::InvalidBuiltinArguments,
+                        "`compensate()` expects a step call and a step function undoing it",
+                    );
+                    return None;
+                };
+                let compensation_id = self.next_step_id(&format!("compensate:{}", step_name));
+                self.current_meta.compensations.push(CompensationMeta {
+                    id: compensation_id.clone(),
+                    step: step_name,
+                    compensates,
+                    location: self.locate(call.span),
+                });
+                Some(codegen::create_ctx_compensate_call(
+                    &self.ctx,
+                    &compensation_id,
+                    (*call.args[0].expr).clone(),
+                    undo,
+                    merge_options(None, step_options),
+                    call.span,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// A step function passed to `map()` or `compensate()`: the step's name,
+    /// the function to hand to the runtime and the step's options. The
+    /// function is the step's body when steps are inlined, otherwise the step
+    /// function itself.
+    fn step_fn_arg(&self, expr: &Expr) -> Option<(String, Expr, StepOptions)> {
+        if let Expr::Ident(ident) = expr {
+            if let Some(step_info) = self.info.step_fns.get(ident.sym.as_ref()) {
+                if self.config.step_strategy == StepStrategy::Closure {
+                    return Some((
+                        step_info.name.clone(),
+                        expr.clone(),
+                        local_step_options(step_info),
+                    ));
+                }
+                let body_stmts = step_info
+                    .body
+                    .stmts
                     .iter()
-                    .map(|arg| ExprOrSpread {
-                        spread: arg.spread,
-                        expr: Box::new(self.transform_expr(&arg.expr)),
-                    })
+                    .filter(|s| !is_use_step_directive(s))
+                    .cloned()
                     .collect();
-
-                Expr::Call(CallExpr {
-                    span: call.span,
-                    callee: call.callee.clone(),
-                    args: new_args,
-                    type_args: call.type_args.clone(),
-                    ctxt: call.ctxt,
-                })
+                return Some((
+                    step_info.name.clone(),
+                    codegen::create_step_fn_expr(
+                        step_info.params.clone(),
+                        body_stmts,
+                        step_info.span,
+                    ),
+                    local_step_options(step_info),
+                ));
             }
-            Expr::Await(await_expr) => {
-                let inner = self.transform_expr(&await_expr.arg);
-                Expr::Await(AwaitExpr {
-                    span: await_expr.span,
-                    arg: Box::new(inner),
-                })
+        }
+        let step_name = self
+            .info
+            .imported_step_callee(&Callee::Expr(Box::new(expr.clone())))?;
+        Some((step_name, expr.clone(), imported_step_options(expr)))
+    }
+
+    /// The id of the durable call `expr` has been rewritten to, such as
+    /// `chargeCard:1` for `ctx.step("chargeCard:1", ...)`, awaited or not.
+    fn durable_call_id(&self, expr: &Expr) -> Option<String> {
+        let call = match expr {
+            Expr::Await(await_expr) => return self.durable_call_id(&await_expr.arg),
+            Expr::Paren(paren) => return self.durable_call_id(&paren.expr),
+            Expr::Call(call) => call,
+            _ => return None,
+        };
+        let Callee::Expr(callee) = &call.callee else {
+            return None;
+        };
+        let Expr::Member(MemberExpr {
+            obj,
+            prop: MemberProp::Ident(method),
+            ..
+        }) = callee.as_ref()
+        else {
+            return None;
+        };
+        match obj.as_ref() {
+            Expr::Ident(obj) if obj.sym == self.ctx.sym => {}
+            _ => return None,
+        }
+        if !["step", "map", "parallel", "compensate"].contains(&method.sym.as_str()) {
+            return None;
+        }
+        match call.args.first().map(|arg| arg.expr.as_ref()) {
+            Some(Expr::Lit(Lit::Str(id))) => Some(id.value.to_string_lossy().into_owned()),
+            _ => None,
+        }
+    }
+
+    /// Rewrite `Promise.all([...])`, `Promise.allSettled([...])` or
+    /// `Promise.race([...])` over step or invoke calls into `ctx.parallel`,
+    /// each array element becoming a branch. Branches are rewritten here, so
+    /// their step ids are handed out after the parallel's own id.
+    fn rewrite_parallel(&mut self, call: &mut CallExpr) -> Option<Expr> {
+        let mode = self.promise_combinator(&call.callee)?;
+        let [arg] = call.args.as_mut_slice() else {
+            return None;
+        };
+        let Expr::Array(array) = &mut *arg.expr else {
+            return None;
+        };
+        let elems: Option<Vec<&mut Box<Expr>>> = array
+            .elems
+            .iter_mut()
+            .map(|elem| match elem {
+                Some(ExprOrSpread { spread: None, expr }) => Some(expr),
+                _ => None,
+            })
+            .collect();
+        let elems = elems?;
+        if !elems.iter().any(|elem| self.has_durable_call(elem)) {
+            return None;
+        }
+
+        let parallel_id = self.next_step_id("parallel");
+        let location = self.locate(call.span);
+        let mut branch_ids = Vec::new();
+        let mut branches = Vec::new();
+        for elem in elems {
+            let (steps, invokes, maps) = (
+                self.current_meta.steps.len(),
+                self.current_meta.invokes.len(),
+                self.current_meta.maps.len(),
+            );
+            elem.visit_mut_with(self);
+            let meta = &self.current_meta;
+            branch_ids.push(
+                meta.steps[steps..]
+                    .iter()
+                    .map(|step| step.id.clone())
+                    .chain(
+                        meta.invokes[invokes..]
+                            .iter()
+                            .map(|invoke| invoke.id.clone()),
+                    )
+                    .chain(meta.maps[maps..].iter().map(|map| map.id.clone()))
+                    .collect(),
+            );
+            branches.push((**elem).clone());
+        }
+        self.current_meta.parallels.push(ParallelMeta {
+            id: parallel_id.clone(),
+            mode,
+            branches: branch_ids,
+            location,
+        });
+        Some(codegen::create_ctx_parallel_call(
+            &self.ctx,
+            &parallel_id,
+            branches,
+            mode,
+            call.span,
+        ))
+    }
+
+    /// `Promise.all`, `Promise.allSettled` or `Promise.race` on the global
+    /// `Promise`.
+    fn promise_combinator(&self, callee: &Callee) -> Option<&'static str> {
+        let Callee::Expr(expr) = callee else {
+            return None;
+        };
+        let Expr::Member(MemberExpr {
+            obj,
+            prop: MemberProp::Ident(prop),
+            ..
+        }) = expr.as_ref()
+        else {
+            return None;
+        };
+        match obj.as_ref() {
+            Expr::Ident(obj) if obj.sym == "Promise" && obj.ctxt == self.info.unresolved_ctxt => {}
+            _ => return None,
+        }
+        ["all", "allSettled", "race"]
+            .into_iter()
+            .find(|mode| prop.sym == *mode)
+    }
+
+    /// Whether `expr` calls a step, maps one or invokes a Lambda anywhere
+    /// within it.
+    fn has_durable_call(&self, expr: &Expr) -> bool {
+        struct DurableCallFinder<'a> {
+            transformer: &'a WorkflowTransformer,
+            found: bool,
+        }
+        impl Visit for DurableCallFinder<'_> {
+            fn visit_call_expr(&mut self, call: &CallExpr) {
+                let transformer = self.transformer;
+                self.found |= transformer.is_step_fn_call(&call.callee).is_some()
+                    || transformer
+                        .info
+                        .imported_step_callee(&call.callee)
+                        .is_some()
+                    || matches!(
+                        transformer.is_special_call(&call.callee),
+                        Some("invoke" | "map")
+                    );
+                call.visit_children_with(self);
             }
-            Expr::Assign(assign) => Expr::Assign(AssignExpr {
-                span: assign.span,
-                op: assign.op,
-                left: assign.left.clone(),
-                right: Box::new(self.transform_expr(&assign.right)),
-            }),
-            // Pass through everything else
-            other => other.clone(),
         }
+
+        let mut finder = DurableCallFinder {
+            transformer: self,
+            found: false,
+        };
+        expr.visit_with(&mut finder);
+        finder.found
+    }
+
+    /// `__stepMeta` entries for the module's exported steps, in source order.
+    fn step_export_metas(&self) -> Vec<StepExportMeta> {
+        let mut steps: Vec<&StepFnInfo> = self
+            .info
+            .step_fns
+            .values()
+            .filter(|step| step.is_exported)
+            .collect();
+        steps.sort_by_key(|step| step.span.lo);
+        steps
+            .into_iter()
+            .map(|step| {
+                let handler = if step.is_default_export {
+                    "default"
+                } else {
+                    &step.public_name
+                };
+                StepExportMeta::new(
+                    &step.public_name,
+                    handler,
+                    self.config.filename.clone(),
+                    &step.params,
+                    step.retry.as_ref().and_then(static_props),
+                    step.timeout.as_ref().and_then(literal_duration),
+                )
+            })
+            .collect()
     }
 
     // ── Client Mode ────────────────────────────────────────────
@@ -363,36 +898,27 @@ impl WorkflowTransformer {
         let mut new_items: Vec<ModuleItem> = Vec::new();
         let mut descriptors_to_add: Vec<ModuleItem> = Vec::new();
 
-        // Collect sources that have workflow imports
-        let workflow_sources: Vec<String> = self
-            .info
-            .workflow_imports
-            .iter()
-            .map(|i| i.source.clone())
-            .collect::<std::collections::HashSet<_>>()
-            .into_iter()
-            .collect();
-
         for item in module.body.drain(..) {
             match &item {
-                ModuleItem::ModuleDecl(ModuleDecl::Import(import)) => {
-                    let src = import.src.value.to_string_lossy().into_owned();
-                    if workflow_sources.contains(&src) {
-                        // Replace this import with descriptors
-                        for spec in &import.specifiers {
-                            let local_name = match spec {
-                                ImportSpecifier::Named(n) => n.local.sym.to_string(),
-                                ImportSpecifier::Default(d) => d.local.sym.to_string(),
-                                ImportSpecifier::Namespace(ns) => ns.local.sym.to_string(),
-                                _ => continue,
-                            };
-                            descriptors_to_add.push(codegen::create_workflow_descriptor(
-                                &local_name,
-                                &self.config.env_prefix,
-                            ));
+                // Replace workflow imports with descriptors
+                ModuleItem::ModuleDecl(ModuleDecl::Import(import))
+                    if is_workflow_import(import, &self.config) =>
+                {
+                    for import_info in workflow_import_specifiers(import) {
+                        if import_info.imported_name == "*" {
+                            match self.create_namespace_descriptors(&import_info) {
+                                Some(namespace) => descriptors_to_add.push(namespace),
+                                None => new_items.push(namespace_import(import, import_info.local)),
+                            }
+                            continue;
                         }
-                    } else {
-                        new_items.push(item);
+                        let workflow_name = self.imported_workflow_name(&import_info);
+                        let env_var_name = self.env_var_name(&workflow_name);
+                        descriptors_to_add.push(codegen::create_workflow_descriptor(
+                            import_info.local,
+                            &workflow_name,
+                            &env_var_name,
+                        ));
                     }
                 }
                 _ => {
@@ -410,11 +936,139 @@ impl WorkflowTransformer {
             .unwrap_or(new_items.len());
 
         final_items.extend(new_items[..first_non_import].iter().cloned());
+        if !descriptors_to_add.is_empty() {
+            final_items.push(codegen::create_client_import(&self.config.package_name));
+        }
         final_items.extend(descriptors_to_add);
         final_items.extend(new_items[first_non_import..].iter().cloned());
 
         module.body = final_items;
     }
+
+    /// Descriptors for every workflow a namespace import can reach, taken from
+    /// the workflow manifest. Without manifest entries the exports are
+    /// unknown, so the import is reported and left in place.
+    fn create_namespace_descriptors(&self, import: &WorkflowImportInfo) -> Option<ModuleItem> {
+        let exports = manifest_module_exports(&self.config, &import.source);
+        if exports.is_empty() {
+            diagnostics::warn(
+                import.local.span,
+                DiagnosticCode::UnresolvedWorkflowNamespace,
+                &format!(
+                    "workflows of \"{}\" are not listed in `workflowManifest`, so the \
+                     namespace import is left in place",
+                    import.source
+                ),
+            );
+            return None;
+        }
+        let exports: Vec<(String, String, String)> = exports
+            .into_iter()
+            .map(|(export_name, workflow_name)| {
+                let env_var_name = self.env_var_name(&workflow_name);
+                (export_name, workflow_name, env_var_name)
+            })
+            .collect();
+        Some(codegen::create_workflow_namespace(
+            import.local.clone(),
+            &exports,
+        ))
+    }
+
+    fn env_var_name(&self, workflow_name: &str) -> String {
+        codegen::workflow_env_var_name(
+            &self.config.env_prefix,
+            self.config.env_naming,
+            workflow_name,
+        )
+    }
+
+    /// Name of the deployed workflow an import refers to. The local alias
+    /// plays no part: `import { signupWorkflow as signup }` is
+    /// `signupWorkflow`.
+    fn imported_workflow_name(&self, import: &WorkflowImportInfo) -> String {
+        if let Some(name) =
+            manifest_workflow_name(&self.config, &import.source, &import.imported_name)
+        {
+            return name;
+        }
+        match import.imported_name.as_str() {
+            // A default export is named after its module, as in workflow mode
+            "default" => workflow_name_from_path(&import.source)
+                .unwrap_or_else(|| import.local.sym.to_string()),
+            imported_name => imported_name.to_string(),
+        }
+    }
+}
+
+/// Removes `"use step"` directives from every block of the output. Exported
+/// steps stay behind as plain functions; their directive has no meaning once
+/// the module is compiled.
+struct StepDirectiveStripper;
+
+impl VisitMut for StepDirectiveStripper {
+    fn visit_mut_block_stmt(&mut self, block: &mut BlockStmt) {
+        block.stmts.retain(|stmt| !is_use_step_directive(stmt));
+        block.visit_mut_children_with(self);
+    }
+}
+
+/// Options of a step's `ctx.step` calls as `(key, value)` pairs, such as
+/// `("retry", policy)`.
+type StepOptions = Vec<(&'static str, Expr)>;
+
+/// Options passed to every `ctx.step` call of a step declared in the module.
+fn local_step_options(step: &StepFnInfo) -> StepOptions {
+    [("retry", &step.retry), ("timeout", &step.timeout)]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value.clone()?)))
+        .collect()
+}
+
+/// Options of a step imported from a step module, read from the step function
+/// they are assigned to there: `{ retry: chargeCard.retry, ... }`.
+fn imported_step_options(step_fn: &Expr) -> StepOptions {
+    ["retry", "timeout"]
+        .into_iter()
+        .map(|key| (key, codegen::create_step_option_ref(step_fn.clone(), key)))
+        .collect()
+}
+
+/// Options passed to `withDurableExecution`.
+fn workflow_options(workflow: &WorkflowFnInfo) -> Option<Expr> {
+    let timeout = workflow.timeout.clone()?;
+    Some(codegen::with_option(None, "timeout", timeout))
+}
+
+/// Add `extra` to an options bag, see [`codegen::with_option`].
+fn merge_options(options: Option<Expr>, extra: StepOptions) -> Option<Expr> {
+    extra.into_iter().fold(options, |options, (key, value)| {
+        Some(codegen::with_option(options, key, value))
+    })
+}
+
+/// A duration literal such as `{ seconds: 30 }`, for metadata.
+fn literal_duration(expr: &Expr) -> Option<serde_json::Value> {
+    static_value(strip_type_wrappers(expr))
+}
+
+/// `import * as local from "<source of import>"`
+fn namespace_import(import: &ImportDecl, local: Ident) -> ModuleItem {
+    ModuleItem::ModuleDecl(ModuleDecl::Import(ImportDecl {
+        specifiers: vec![ImportSpecifier::Namespace(ImportStarAsSpecifier {
+            span: local.span,
+            local,
+        })],
+        ..import.clone()
+    }))
+}
+
+fn extract_fn_span(expr: &Expr) -> Option<Span> {
+    match expr {
+        Expr::Arrow(arrow) => Some(arrow.span),
+        Expr::Fn(fn_expr) => Some(fn_expr.function.span),
+        _ => None,
+    }
 }
 
 fn extract_arrow_body(expr: &Expr) -> Option<BlockStmt> {
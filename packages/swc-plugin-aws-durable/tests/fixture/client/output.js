@@ -1,9 +1,9 @@
 import { logger } from "shared-utils";
-const signupWorkflow = {
-    __workflow: true,
+import { defineWorkflow } from "@cgalceran/aws-durable";
+const signupWorkflow = defineWorkflow({
     name: "signupWorkflow",
-    functionName: process.env.WORKFLOW_SIGNUPWORKFLOW
-};
+    functionName: process.env.WORKFLOW_SIGNUP_WORKFLOW
+});
 export async function handleRequest(req) {
     logger.info("Starting signup");
     const result = await signupWorkflow({
@@ -1,20 +1,21 @@
 import { withDurableExecution } from "@cgalceran/aws-durable";
 import { LambdaClient, InvokeCommand } from "@aws-sdk/client-lambda";
 export const signupWorkflow = withDurableExecution(async (event, ctx)=>{
-    const email = await ctx.step("validateEmail", async ()=>{
-        if (!event.email.includes("@")) throw new Error("Invalid email");
-        return event.email.toLowerCase();
-    });
+    const input = event.input;
+    const email = await ctx.step("validateEmail:1", ((email)=>async ()=>{
+            if (!email.includes("@")) throw new Error("Invalid email");
+            return email.toLowerCase();
+        })(input.email));
     await ctx.wait({
         seconds: 5
     });
-    const result = await ctx.step("invoke", async ()=>{
+    const result = await ctx.step("invoke:sendWelcomeEmail:1", async ()=>{
         const client = new LambdaClient({});
         const response = await client.send(new InvokeCommand({
             FunctionName: "sendWelcomeEmail",
             Payload: JSON.stringify({
                 email,
-                name: event.name
+                name: input.name
             })
         }));
         return JSON.parse(new TextDecoder().decode(response.Payload));
@@ -26,8 +27,57 @@ export const signupWorkflow = withDurableExecution(async (event, ctx)=>{
     };
 });
 export const __workflowMeta = {
-    name: "signupWorkflow",
-    steps: [
-        "validateEmail"
-    ]
+    signupWorkflow: {
+        name: "signupWorkflow",
+        handler: "signupWorkflow",
+        file: "workflows/signup.ts",
+        params: [
+            {
+                role: "input",
+                binding: "input",
+                hasDefault: false,
+                rest: false
+            }
+        ],
+        timeout: null,
+        stepIdScheme: "callsite-ordinal",
+        steps: [
+            {
+                name: "validateEmail",
+                id: "validateEmail:1",
+                retry: null,
+                timeout: null,
+                location: {
+                    line: 9,
+                    column: 25
+                }
+            }
+        ],
+        invokes: [
+            {
+                functionName: "sendWelcomeEmail",
+                id: "invoke:sendWelcomeEmail:1",
+                location: {
+                    line: 11,
+                    column: 26
+                }
+            }
+        ],
+        sleeps: [
+            {
+                duration: {
+                    seconds: 5
+                },
+                location: {
+                    line: 10,
+                    column: 11
+                }
+            }
+        ],
+        callbacks: [],
+        parallels: [],
+        maps: [],
+        compensations: []
+    }
 };
+export const __workflowManifest = "{\"version\":1,\"workflows\":{\"signupWorkflow\":{\"name\":\"signupWorkflow\",\"handler\":\"signupWorkflow\",\"file\":\"workflows/signup.ts\",\"params\":[{\"role\":\"input\",\"binding\":\"input\",\"hasDefault\":false,\"rest\":false}],\"timeout\":null,\"stepIdScheme\":\"callsite-ordinal\",\"steps\":[{\"name\":\"validateEmail\",\"id\":\"validateEmail:1\",\"retry\":null,\"timeout\":null,\"location\":{\"line\":9,\"column\":25}}],\"invokes\":[{\"functionName\":\"sendWelcomeEmail\",\"id\":\"invoke:sendWelcomeEmail:1\",\"location\":{\"line\":11,\"column\":26}}],\"sleeps\":[{\"duration\":{\"seconds\":5},\"location\":{\"line\":10,\"column\":11}}],\"callbacks\":[],\"parallels\":[],\"maps\":[],\"compensations\":[]}}}";